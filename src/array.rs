@@ -1,82 +1,201 @@
-pub struct Array<T, const N: usize> {
-    buf: [T; N],
+use std::collections::VecDeque;
+
+/// Ring buffer with a capacity fixed at creation, that drops the oldest value once full.
+///
+/// Every value is stored twice, once in each half of the buffer. That way the current content is
+/// always available as one contiguous slice while pushing stays O(1).
+pub struct Array<T> {
+    buf: Box<[T]>,
+    capacity: usize,
+    head: usize,
     len: usize,
 }
 
-impl<T, const N: usize> Array<T, N>
+impl<T> Array<T>
 where
     T: Copy,
 {
-    pub fn new(default: T) -> Self {
+    pub fn new(default: T, capacity: usize) -> Self {
         Self {
-            buf: [default; N],
+            buf: vec![default; capacity * 2].into_boxed_slice(),
+            capacity,
+            head: 0,
             len: 0,
         }
     }
 
     pub fn push(&mut self, value: T) {
-        match N {
-            0 => {}
-            1 => {
-                self.buf[0] = value;
-                self.len = 1;
-            }
-            _ => {
-                if self.len < N {
-                    self.buf[self.len] = value;
-                    self.len += 1;
-                } else {
-                    self.buf.copy_within(1..N, 0);
-                    self.buf[N - 1] = value;
-                }
-            }
+        let cap = self.capacity;
+        if cap == 0 {
+            return;
         }
+
+        let pos = if self.len < cap {
+            self.len += 1;
+            (self.head + self.len - 1) % cap
+        } else {
+            let pos = self.head;
+            self.head = (self.head + 1) % cap;
+            pos
+        };
+
+        self.buf[pos] = value;
+        self.buf[pos + cap] = value;
+    }
+
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
     }
 
     pub fn as_slice(&self) -> &[T] {
-        &self.buf[..self.len]
+        &self.buf[self.head..self.head + self.len]
     }
 }
 
-impl<T, const N: usize> Array<T, N>
+impl<T> Array<T>
 where
     T: Default + Copy,
 {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::new(T::default(), capacity)
+    }
+
     pub fn first(&self) -> T {
-        match N {
-            0 => T::default(),
-            _ => {
-                if self.len > 0 {
-                    self.buf[0]
-                } else {
-                    T::default()
-                }
-            }
-        }
+        self.as_slice().first().copied().unwrap_or_default()
     }
 
     pub fn last(&self) -> T {
-        match N {
-            0 => T::default(),
-            _ => {
-                if self.len > 0 {
-                    self.buf[self.len - 1]
-                } else {
-                    T::default()
-                }
-            }
-        }
+        self.as_slice().last().copied().unwrap_or_default()
     }
 }
 
-impl<T, const N: usize> Default for Array<T, N>
+/// Tracker for the minimum and maximum of the last `capacity` pushed values, matching the content
+/// of an [`Array`] with the same capacity.
+///
+/// Both extremes are kept in monotonic queues, so each value is only inserted and removed once,
+/// instead of scanning the whole window on every update.
+pub struct MinMax<T> {
+    capacity: usize,
+    count: usize,
+    min: VecDeque<(usize, T)>,
+    max: VecDeque<(usize, T)>,
+}
+
+impl<T> MinMax<T>
 where
-    T: Default + Copy,
+    T: PartialOrd + Copy,
 {
-    fn default() -> Self {
+    pub fn new(capacity: usize) -> Self {
         Self {
-            buf: [Default::default(); N],
-            len: Default::default(),
+            capacity,
+            count: 0,
+            min: VecDeque::new(),
+            max: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        while self.min.back().is_some_and(|(_, v)| *v >= value) {
+            self.min.pop_back();
+        }
+        while self.max.back().is_some_and(|(_, v)| *v <= value) {
+            self.max.pop_back();
+        }
+
+        self.min.push_back((self.count, value));
+        self.max.push_back((self.count, value));
+        self.count += 1;
+
+        let oldest = self.count.saturating_sub(self.capacity);
+        while self.min.front().is_some_and(|(i, _)| *i < oldest) {
+            self.min.pop_front();
+        }
+        while self.max.front().is_some_and(|(i, _)| *i < oldest) {
+            self.max.pop_front();
+        }
+    }
+
+    pub fn min(&self) -> Option<T> {
+        self.min.front().map(|(_, v)| *v)
+    }
+
+    pub fn max(&self) -> Option<T> {
+        self.max.front().map(|(_, v)| *v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{Array, MinMax};
+
+    /// The original shifting implementation, used as reference for the ring buffer.
+    struct Shifting {
+        buf: Vec<u32>,
+        capacity: usize,
+    }
+
+    impl Shifting {
+        fn push(&mut self, value: u32) {
+            match self.capacity {
+                0 => {}
+                1 => {
+                    self.buf.clear();
+                    self.buf.push(value);
+                }
+                n => {
+                    if self.buf.len() < n {
+                        self.buf.push(value);
+                    } else {
+                        self.buf.copy_within(1..n, 0);
+                        self.buf[n - 1] = value;
+                    }
+                }
+            }
+        }
+
+        fn as_slice(&self) -> &[u32] {
+            &self.buf
+        }
+    }
+
+    fn check(capacity: usize, values: &[u32]) -> Result<(), TestCaseError> {
+        let mut array = Array::<u32>::with_capacity(capacity);
+        let mut minmax = MinMax::<u32>::new(capacity);
+        let mut reference = Shifting {
+            buf: Vec::new(),
+            capacity,
+        };
+
+        for value in values.iter().copied() {
+            array.push(value);
+            minmax.push(value);
+            reference.push(value);
+
+            let expect = reference.as_slice();
+            prop_assert_eq!(expect, array.as_slice());
+            prop_assert_eq!(expect.first().copied().unwrap_or_default(), array.first());
+            prop_assert_eq!(expect.last().copied().unwrap_or_default(), array.last());
+            prop_assert_eq!(expect.iter().min().copied(), minmax.min());
+            prop_assert_eq!(expect.iter().max().copied(), minmax.max());
+        }
+
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn same_as_shifting(
+            capacity in 0..80_usize,
+            values in prop::collection::vec(0..100_u32, 0..300),
+        ) {
+            check(capacity, &values)?;
         }
     }
 }
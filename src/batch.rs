@@ -0,0 +1,650 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use time::{Duration, Instant};
+use tui::{
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Paragraph},
+};
+
+use crate::{
+    ffprobe::Format,
+    format_duration, format_size,
+    guard::{self, SizeGuard},
+    hooks,
+    keep::KeepSmaller,
+    notify::Finish,
+    stats::{self, Event, Stats},
+    verify::Verify,
+    Header,
+};
+
+/// Expand the given list of inputs into the list of files to encode. Directories are replaced by
+/// the files they contain (non-recursive), and arguments with wildcards are treated as glob
+/// patterns, unless they name an existing file, like `Show [1080p].mkv`.
+pub fn collect_inputs(inputs: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for input in inputs {
+        if input.is_dir() {
+            let mut entries = fs::read_dir(input)?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            entries.retain(|path| path.is_file() && !is_stats_file(path));
+            entries.sort();
+            files.extend(entries);
+        } else if input.is_file() {
+            files.push(input.clone());
+        } else if input.to_string_lossy().contains(['*', '?', '[']) {
+            let pattern = input.to_str().context("glob pattern is not valid UTF-8")?;
+            for path in glob::glob(pattern)? {
+                let path = path?;
+                if path.is_file() && !is_stats_file(&path) {
+                    files.push(path);
+                }
+            }
+        } else {
+            bail!("input `{}` doesn't exist", input.display());
+        }
+    }
+
+    ensure!(!files.is_empty(), "no input files found");
+
+    Ok(files)
+}
+
+fn is_stats_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "stats")
+}
+
+/// Determine the output location for an input, placing it in the output directory with the same
+/// name, optionally replacing the file extension.
+pub fn output_path(input: &Path, dir: &Path, extension: Option<&str>) -> Result<PathBuf> {
+    let name = input.file_name().context("input without file name")?;
+    let mut output = dir.join(name);
+
+    if let Some(extension) = extension {
+        output.set_extension(extension);
+    }
+
+    ensure!(
+        fs::canonicalize(input).ok() != fs::canonicalize(&output).ok() || !output.exists(),
+        "output `{}` would overwrite its input",
+        output.display()
+    );
+
+    Ok(output)
+}
+
+/// Single encoding in a batch.
+pub struct Job {
+    /// Position of the job in the [`Journal`].
+    pub id: usize,
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub format: Format,
+    /// Arguments to pass to FFmpeg, with all placeholders already filled in.
+    pub args: Vec<String>,
+}
+
+/// Persisted state of a batch run, written to disk after every change, so an interrupted run can
+/// be resumed later.
+pub struct Journal {
+    path: PathBuf,
+    content: JournalContent,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JournalContent {
+    #[serde(flatten)]
+    options: Options,
+    entries: Vec<Entry>,
+}
+
+/// Settings of a batch run, persisted with the journal so a resumed run behaves the same.
+#[derive(Serialize, Deserialize)]
+pub struct Options {
+    /// Configuration file of the run, to load the same binaries and hooks again when resuming.
+    #[serde(default)]
+    pub config: Option<PathBuf>,
+    pub overwrite: bool,
+    /// Amount of jobs to run concurrently.
+    pub jobs: u16,
+    #[serde(default)]
+    pub size_guard: Option<SizeGuard>,
+    #[serde(default)]
+    pub keep_smaller: Option<KeepSmaller>,
+    #[serde(default)]
+    pub verify: Option<Verify>,
+    /// Fully decode each output after encoding, to find any corrupt frames.
+    #[serde(default)]
+    pub check_integrity: bool,
+    /// Compare each output with its input after encoding, measuring its quality.
+    #[serde(default)]
+    pub quality: bool,
+}
+
+/// Record of a single job in the journal.
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub args: Vec<String>,
+    pub status: Status,
+    /// Location of the statistics file, once the job is done.
+    pub stats: Option<PathBuf>,
+}
+
+impl Entry {
+    /// New entry for a job that still has to run.
+    pub fn pending(input: PathBuf, output: PathBuf, args: Vec<String>) -> Self {
+        Self {
+            input,
+            output,
+            args,
+            status: Status::Pending,
+            stats: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Pending,
+    Running,
+    Done,
+    Failed(String),
+}
+
+impl Journal {
+    /// Default location of the journal, inside the user's local data directory.
+    pub fn default_path() -> Result<PathBuf> {
+        dirs::data_local_dir()
+            .map(|dir| dir.join(env!("CARGO_PKG_NAME")).join("queue.json"))
+            .context("failed to locate the local data directory")
+    }
+
+    /// Create a new journal for the given entries and write it to disk, replacing any previous
+    /// one.
+    pub fn create(path: PathBuf, entries: Vec<Entry>, options: Options) -> Result<Self> {
+        let journal = Self {
+            path,
+            content: JournalContent { options, entries },
+        };
+
+        journal.save()?;
+
+        Ok(journal)
+    }
+
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let content = fs::read(&path)
+            .with_context(|| format!("failed reading the queue from `{}`", path.display()))?;
+        let content = serde_json::from_slice(&content)
+            .with_context(|| format!("invalid queue file `{}`", path.display()))?;
+
+        Ok(Self { path, content })
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.content.options
+    }
+
+    /// Whether any jobs still wait to run, or were interrupted while running.
+    pub fn is_unfinished(&self) -> bool {
+        self.content
+            .entries
+            .iter()
+            .any(|entry| matches!(entry.status, Status::Pending | Status::Running))
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.content.entries
+    }
+
+    /// Update the status of a job, and persist the change right away.
+    pub fn update(&mut self, id: usize, status: Status) -> Result<()> {
+        self.content.entries[id].status = status;
+        self.save()
+    }
+
+    /// Record a job as done, together with the location of its statistics.
+    pub fn complete(&mut self, id: usize, stats: PathBuf) -> Result<()> {
+        let entry = &mut self.content.entries[id];
+        entry.status = Status::Done;
+        entry.stats = Some(stats);
+
+        self.save()
+    }
+
+    /// Prepare the journal for resumption. Jobs that were interrupted are reset, removing any
+    /// partially written output, and finished jobs whose output disappeared are scheduled again,
+    /// unless the output was moved away by the keep-smaller step. Failed jobs are only retried if
+    /// requested.
+    ///
+    /// Returns the ids of all jobs that need to run.
+    pub fn resume(&mut self, retry_failed: bool) -> Result<Vec<usize>> {
+        let mut ids = Vec::new();
+
+        let moves_output = self.content.options.keep_smaller.is_some();
+
+        for (id, entry) in self.content.entries.iter_mut().enumerate() {
+            let run = match entry.status {
+                Status::Pending => true,
+                Status::Running => {
+                    if entry.output.exists() {
+                        fs::remove_file(&entry.output).with_context(|| {
+                            format!(
+                                "failed removing the partial output `{}`",
+                                entry.output.display()
+                            )
+                        })?;
+                    }
+                    true
+                }
+                Status::Done => !moves_output && !entry.output.exists(),
+                Status::Failed(_) => retry_failed,
+            };
+
+            if run {
+                entry.status = Status::Pending;
+                entry.stats = None;
+                ids.push(id);
+            }
+        }
+
+        self.save()?;
+
+        Ok(ids)
+    }
+
+    /// Write the journal to a temporary file first and then move it in place, so a crash doesn't
+    /// leave a half-written file behind.
+    fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&self.content)?)?;
+        fs::rename(&tmp, &self.path)?;
+
+        Ok(())
+    }
+}
+
+/// Running batch, combining the in-memory queue shown in the UI with the persisted journal.
+pub struct Session {
+    pub queue: Queue,
+    journal: Journal,
+}
+
+impl Session {
+    pub fn new(journal: Journal, jobs: &[Job]) -> Self {
+        Self {
+            queue: Queue::new(jobs.iter().map(|job| job.format.duration).collect()),
+            journal,
+        }
+    }
+
+    pub fn options(&self) -> &Options {
+        self.journal.options()
+    }
+
+    pub fn start(&mut self, job: &Job) -> Result<()> {
+        hooks::start(&job.input, Some(&job.output), job.format.duration);
+        self.journal.update(job.id, Status::Running)
+    }
+
+    /// Save the statistics of a completed job and record it as done, or as failed if it was
+    /// aborted early or its verification failed. The partial output of an aborted job is deleted.
+    /// If enabled, only the smaller of input and output is kept afterwards.
+    ///
+    /// Returns whether the job succeeded.
+    pub fn finish(&mut self, index: usize, job: Job, mut stats: Stats) -> Result<bool> {
+        if stats.events.iter().any(Event::is_abort) {
+            if let Err(e) = guard::remove_partial(&job.output) {
+                stats::save(&stats, &job.input)?;
+                self.fail(index, job, &e)?;
+                return Ok(false);
+            }
+        }
+
+        let mut output_size = fs::metadata(&job.output).map_or_else(
+            |_| {
+                stats
+                    .history
+                    .last()
+                    .and_then(|(_, p)| p.total_size)
+                    .unwrap_or(0)
+            },
+            |m| m.len(),
+        );
+        let input_size = stats.import.size;
+        // Taken before the keep-smaller step, to describe the encoding, not what was kept.
+        let size_ratio = (input_size > 0).then(|| output_size as f64 / input_size as f64);
+
+        let options = self.journal.options();
+        // The statistics belong to whatever took the input's place.
+        let mut kept = job.input.clone();
+
+        if let (Some(verify), None) = (options.verify, stats.failure()) {
+            stats.events.push(verify.run(&stats.import, &job.output));
+        }
+
+        if let (Some(keep), None) = (&options.keep_smaller, stats.failure()) {
+            let event = match keep.apply(&job.input, input_size, &job.output) {
+                Ok((event, path)) => {
+                    kept = path;
+                    event
+                }
+                Err(e) => {
+                    stats::save(&stats, &job.input)?;
+                    self.fail(index, job, &e)?;
+                    return Ok(false);
+                }
+            };
+
+            // Keeping the input means nothing was saved.
+            if let Event::KeepSmaller {
+                kept_output: false,
+                dry_run: false,
+                ..
+            } = event
+            {
+                output_size = input_size;
+            }
+
+            stats.events.push(event);
+        }
+
+        stats::save(&stats, &kept)?;
+
+        let stats_path = stats::path(&kept);
+
+        if let Some(event) = stats.failure() {
+            let error = event.to_string();
+
+            hooks::finish(
+                &job.input,
+                &Finish::Failed(error.clone()),
+                size_ratio,
+                Some(&stats_path),
+            );
+            self.journal.update(job.id, Status::Failed(error.clone()))?;
+            self.queue.finish(index, job.input, Outcome::Failed(error));
+
+            return Ok(false);
+        }
+
+        hooks::finish(
+            &job.input,
+            &Finish::Completed,
+            size_ratio,
+            Some(&stats_path),
+        );
+        self.journal.complete(job.id, stats_path)?;
+        self.queue.finish(
+            index,
+            job.input,
+            Outcome::Done {
+                input_size,
+                output_size,
+            },
+        );
+
+        Ok(true)
+    }
+
+    /// Record a job as failed that couldn't even be started, like one with an unreadable input.
+    pub fn skip(&mut self, id: usize, error: &anyhow::Error) -> Result<()> {
+        let error = format!("{error:#}");
+        let input = self.journal.entries()[id].input.clone();
+
+        self.journal.update(id, Status::Failed(error.clone()))?;
+        self.queue.skip(input, Outcome::Failed(error));
+
+        Ok(())
+    }
+
+    pub fn fail(&mut self, index: usize, job: Job, error: &anyhow::Error) -> Result<()> {
+        let error = format!("{error:#}");
+
+        hooks::finish(&job.input, &Finish::Failed(error.clone()), None, None);
+        self.journal.update(job.id, Status::Failed(error.clone()))?;
+        self.queue.finish(index, job.input, Outcome::Failed(error));
+
+        Ok(())
+    }
+}
+
+/// Outcome of a single job in the queue.
+pub enum Outcome {
+    Done { input_size: u64, output_size: u64 },
+    Failed(String),
+}
+
+/// Overall state of a batch run, tracking finished jobs and the work that is left. Jobs are named
+/// by their input file, unless they encode something else, like the samples of a trial.
+pub struct Queue<L = PathBuf> {
+    total: usize,
+    durations: Vec<Duration>,
+    outcomes: Vec<(L, Outcome)>,
+    start_time: Instant,
+    processed: Duration,
+}
+
+impl<L> Queue<L> {
+    /// Create a new queue, with the media durations of all jobs, used to estimate the remaining
+    /// time.
+    pub fn new(durations: Vec<Duration>) -> Self {
+        Self {
+            total: durations.len(),
+            durations,
+            outcomes: Vec::new(),
+            start_time: Instant::now(),
+            processed: Duration::ZERO,
+        }
+    }
+
+    /// Record the outcome of the job at the given position in the queue.
+    pub fn finish(&mut self, job: usize, label: L, outcome: Outcome) {
+        if let Some(duration) = self.durations.get(job) {
+            self.processed += *duration;
+        }
+
+        self.outcomes.push((label, outcome));
+    }
+
+    /// Record the outcome of a job that was never part of the queue's estimates.
+    pub fn skip(&mut self, label: L, outcome: Outcome) {
+        self.total += 1;
+        self.outcomes.push((label, outcome));
+    }
+
+    pub fn outcomes(&self) -> &[(L, Outcome)] {
+        &self.outcomes
+    }
+
+    pub fn failed(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|(_, o)| matches!(o, Outcome::Failed(_)))
+            .count()
+    }
+
+    /// Total amount of bytes saved so far, being negative if the outputs are larger than the
+    /// inputs.
+    pub fn savings(&self) -> (i64, u64) {
+        self.outcomes
+            .iter()
+            .fold((0, 0), |(saved, total), (_, outcome)| match outcome {
+                Outcome::Done {
+                    input_size,
+                    output_size,
+                } => (
+                    saved + *input_size as i64 - *output_size as i64,
+                    total + input_size,
+                ),
+                Outcome::Failed(_) => (saved, total),
+            })
+    }
+
+    /// Estimate the remaining time of the whole queue, based on the throughput of media time per
+    /// wall-clock time so far. The media time already processed by any running jobs is given as
+    /// `in_progress`.
+    pub fn eta(&self, in_progress: Duration) -> Option<Duration> {
+        let processed = self.processed + in_progress;
+        let elapsed = self.start_time.elapsed();
+        if processed <= Duration::ZERO || elapsed <= Duration::ZERO {
+            return None;
+        }
+
+        let total = self.durations.iter().copied().sum::<Duration>();
+        let remaining = (total - processed).max(Duration::ZERO);
+
+        Some(remaining * (elapsed.as_seconds_f64() / processed.as_seconds_f64()))
+    }
+}
+
+impl<L> Header for Queue<L> {
+    fn create(&self, in_progress: Duration, title: &str) -> Paragraph<'_> {
+        let (saved, total) = self.savings();
+        let ratio = if total > 0 {
+            saved as f64 / total as f64 * 100.0
+        } else {
+            0.0
+        };
+        let label = Style::default().fg(Color::Blue);
+
+        Paragraph::new(Spans::from(vec![
+            Span::styled("Done: ", label),
+            Span::raw(format!(
+                "{} / {} ({} failed)",
+                self.outcomes.len(),
+                self.total,
+                self.failed()
+            )),
+            Span::styled("  Saved: ", label),
+            Span::raw(format!(
+                "{}{} ({ratio:.1}%)",
+                if saved < 0 { "-" } else { "" },
+                format_size(saved.unsigned_abs())
+            )),
+            Span::styled("  Total ETA: ", label),
+            Span::raw(
+                self.eta(in_progress)
+                    .map_or_else(|| "--:--:--".to_owned(), format_duration),
+            ),
+        ]))
+        .block(
+            Block::default()
+                .title(Span::styled(format!("Queue / {title}"), label))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use time::Duration;
+
+    use super::{Journal, Outcome, Queue, Status};
+
+    #[test]
+    fn savings_skip_failures() {
+        let mut queue = Queue::<PathBuf>::new(vec![Duration::MINUTE; 3]);
+        queue.finish(
+            0,
+            "a".into(),
+            Outcome::Done {
+                input_size: 100,
+                output_size: 40,
+            },
+        );
+        queue.finish(1, "b".into(), Outcome::Failed("broken".to_owned()));
+        queue.finish(
+            2,
+            "c".into(),
+            Outcome::Done {
+                input_size: 50,
+                output_size: 70,
+            },
+        );
+
+        queue.skip("d".into(), Outcome::Failed("unreadable".to_owned()));
+
+        assert_eq!((40, 150), queue.savings());
+        assert_eq!(2, queue.failed());
+        assert_eq!(4, queue.outcomes().len());
+    }
+
+    #[test]
+    fn collect_bracketed_names() {
+        let dir = std::env::temp_dir().join(format!("ffprog-inputs-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let files = ["Show [1080p].mkv", "Show 1.mkv", "Show p.mkv"].map(|name| dir.join(name));
+        for file in &files {
+            fs::write(file, "").unwrap();
+        }
+
+        let collected = super::collect_inputs(&[files[0].clone()]).unwrap();
+        let globbed = super::collect_inputs(&[dir.join("Show [0-9].mkv")]).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(vec![files[0].clone()], collected);
+        assert_eq!(vec![files[1].clone()], globbed);
+    }
+
+    #[test]
+    fn resume_journal() {
+        let dir = std::env::temp_dir().join(format!("ffprog-journal-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let outputs = ["done", "gone", "partial", "failed", "pending"].map(|name| dir.join(name));
+        fs::write(&outputs[0], "").unwrap();
+        fs::write(&outputs[2], "").unwrap();
+
+        let path = dir.join("queue.json");
+        let content = serde_json::json!({
+            "overwrite": false,
+            "jobs": 2,
+            "entries": outputs.iter().zip([
+                serde_json::json!("done"),
+                serde_json::json!("done"),
+                serde_json::json!("running"),
+                serde_json::json!({ "failed": "broken" }),
+                serde_json::json!("pending"),
+            ]).map(|(output, status)| serde_json::json!({
+                "input": "in.mkv",
+                "output": output,
+                "args": [],
+                "status": status,
+                "stats": null,
+            })).collect::<Vec<_>>(),
+        });
+        fs::write(&path, content.to_string()).unwrap();
+
+        let mut journal = Journal::load(path.clone()).unwrap();
+        assert_eq!(vec![1, 2, 4], journal.resume(false).unwrap());
+        assert!(!outputs[2].exists());
+
+        journal.complete(1, "in.mkv.stats".into()).unwrap();
+        let journal = Journal::load(path).unwrap();
+        assert!(matches!(journal.entries()[1].status, Status::Done));
+        assert!(matches!(journal.entries()[3].status, Status::Failed(_)));
+        assert!(journal.entries()[1].stats.is_some());
+
+        fs::remove_dir_all(dir).ok();
+    }
+}
@@ -1,13 +1,29 @@
 use std::{
     fs::OpenOptions,
     io::{self, Write},
+    mem,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use anyhow::{ensure, Context, Result};
-use clap::{Args, CommandFactory, Parser, Subcommand, ValueHint};
+use clap::{ArgGroup, Args, CommandFactory, Parser, Subcommand, ValueHint};
 use clap_complete::Shell;
 
+use crate::{
+    batch::Options,
+    config::{self, Config, Preset},
+    ffmpeg::{self, ProgressSource},
+    guard::SizeGuard,
+    keep::{KeepMode, KeepSmaller},
+    notify::Channel,
+    priority::Priority,
+    search::{self, Target},
+    tools::Tools,
+    values::Smoothing,
+    verify::Verify,
+};
+
 /// Visualizer for the FFmpeg encoding process.
 #[derive(Parser)]
 #[command(
@@ -22,6 +38,14 @@ pub struct Cli {
     pub cmd: Option<Command>,
     #[command(flatten)]
     pub run: Option<RunArgs>,
+    #[command(flatten)]
+    pub live: LiveArgs,
+    #[command(flatten)]
+    pub tools: ToolArgs,
+    #[command(flatten)]
+    pub priority: Priority,
+    #[command(flatten)]
+    pub notify: NotifyArgs,
 }
 
 impl Cli {
@@ -32,6 +56,19 @@ impl Cli {
 
 #[derive(Subcommand)]
 pub enum Command {
+    /// Encode many files in one session, one after another.
+    Batch(Box<BatchArgs>),
+    /// Encode a few short samples of the input, to quickly estimate the size, bitrate and speed of
+    /// the full encoding.
+    Trial(TrialArgs),
+    /// Find the highest quality or smallest file that meets a target, by encoding samples with
+    /// different CRF values, and then encode the full file with the chosen one.
+    CrfSearch(Box<CrfSearchArgs>),
+    /// Manage the persisted queue of the last batch run.
+    Queue {
+        #[command(subcommand)]
+        cmd: QueueCommand,
+    },
     /// Generate auto-completion scripts for various shells.
     Completions {
         /// Shell to generate an auto-completion script for.
@@ -51,25 +88,715 @@ pub enum Command {
 #[derive(Args)]
 pub struct RunArgs {
     /// Same input media file that is used in the FFmpeg arguments.
-    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    // Added to the group by hand, as clap leaves it empty for structs that flatten others, and
+    // then can't tell whether the run arguments were given.
+    #[arg(short, long, group = "RunArgs", value_hint = ValueHint::FilePath)]
     pub input: PathBuf,
-    /// Overwrite the output file if it already exists.
-    #[arg(short = 'y', long)]
-    pub overwrite: bool,
+    /// Output media file, filled into the `{output}` placeholder of the preset.
+    #[arg(requires = "preset", value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    #[command(flatten)]
+    pub preset: PresetArgs,
+    #[command(flatten)]
+    pub overwrite: OverwriteArgs,
     /// Only load the statistics and display them, skipping any encoding.
     #[arg(short = 's', long)]
     pub load_stats: bool,
     /// Show the statistics screen after the encoding is done.
-    #[arg(long)]
+    #[arg(long, overrides_with = "no_show_stats")]
     pub show_stats: bool,
+    /// Don't show the statistics screen, even if the configuration enables it.
+    #[arg(long, overrides_with = "show_stats")]
+    no_show_stats: bool,
     /// Save the statistics to a file, so they can be loaded afterwards.
-    #[arg(long)]
+    #[arg(long, overrides_with = "no_save_stats")]
     pub save_stats: bool,
+    /// Don't save the statistics, even if the configuration enables it.
+    #[arg(long, overrides_with = "save_stats")]
+    no_save_stats: bool,
+    /// Encode in two passes, first analyzing the input and then encoding it with the findings.
+    /// The `-pass` and `-passlogfile` options are added to the FFmpeg arguments, and the first
+    /// pass discards its output.
+    #[arg(long)]
+    pub two_pass: bool,
+    #[command(flatten)]
+    pub handling: OutputArgs,
     /// Arguments to pass to FFmpeg.
     #[arg(raw = true)]
     pub args: Vec<String>,
 }
 
+impl RunArgs {
+    /// Merge the configuration into these arguments. Flags enabled in the config defaults are
+    /// turned on, and if a preset is selected, its arguments are combined with the explicitly
+    /// given ones.
+    pub fn apply_config(&mut self, config: &Config) -> Result<()> {
+        self.overwrite.apply_config(config);
+        self.show_stats = flag(
+            self.show_stats,
+            self.no_show_stats,
+            config.defaults.show_stats,
+        );
+        self.save_stats = flag(
+            self.save_stats,
+            self.no_save_stats,
+            config.defaults.save_stats,
+        );
+
+        if let Some((name, preset)) = self.preset.get(config)? {
+            ensure!(
+                !config::has_output(&preset.args) || self.output.is_some(),
+                "preset `{name}` requires an output file"
+            );
+
+            let input = self
+                .input
+                .to_str()
+                .context("input path is not valid UTF-8")?;
+            let output = match &self.output {
+                Some(output) => Some(output.to_str().context("output path is not valid UTF-8")?),
+                None => None,
+            };
+
+            let args =
+                config::fill_template(&preset.args, input, output, mem::take(&mut self.args));
+            self.args = args;
+        }
+
+        // Both passes of a two-pass encoding always overwrite their output.
+        ffmpeg::check_options(&self.args, self.overwrite.enabled() || self.two_pass)
+    }
+}
+
+#[derive(Args)]
+pub struct BatchArgs {
+    /// Input media files, directories or glob patterns. Directories are expanded to all the files
+    /// they contain, without descending into sub-directories.
+    #[arg(required = true, value_hint = ValueHint::AnyPath)]
+    pub inputs: Vec<PathBuf>,
+    /// Directory to place the encoded files into, keeping their original file name.
+    #[arg(short, long, value_hint = ValueHint::DirPath)]
+    pub output_dir: PathBuf,
+    /// Replace the file extension of the output files.
+    #[arg(short, long)]
+    pub extension: Option<String>,
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    #[command(flatten)]
+    pub preset: PresetArgs,
+    #[command(flatten)]
+    pub overwrite: OverwriteArgs,
+    /// Amount of jobs to run concurrently. With more than one, a dashboard of all jobs is shown,
+    /// where each job can be focused to see its full progress.
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    pub jobs: u16,
+    #[command(flatten)]
+    pub output: OutputArgs,
+    /// Location of the file to persist the queue state in, instead of the default one in the
+    /// user's data directory.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub queue_file: Option<PathBuf>,
+    /// Start a new batch even if the queue file still contains unfinished jobs, dropping them.
+    #[arg(long)]
+    pub force: bool,
+    /// Argument template to pass to FFmpeg, with `{input}` and `{output}` placeholders for each
+    /// file. If a preset is used, these are extra arguments instead.
+    #[arg(raw = true)]
+    pub args: Vec<String>,
+    /// Template, resolved from either the preset or the explicit arguments.
+    #[arg(skip)]
+    template: Vec<String>,
+}
+
+impl BatchArgs {
+    /// Settings of the batch run, that are persisted in the journal.
+    pub fn options(&self) -> Result<Options> {
+        Ok(Options {
+            config: self
+                .config
+                .path
+                .as_deref()
+                .map(std::path::absolute)
+                .transpose()
+                .context("failed resolving the configuration path")?,
+            overwrite: self.overwrite.enabled(),
+            jobs: self.jobs,
+            size_guard: self.output.guard.size_guard(),
+            keep_smaller: self.output.keep.keep_smaller()?,
+            verify: self.output.verify.verify(),
+            check_integrity: self.output.passes.check_integrity,
+            quality: self.output.passes.quality,
+        })
+    }
+
+    /// Merge the configuration into these arguments, resolving the argument template from the
+    /// preset if one is selected.
+    pub fn apply_config(&mut self, config: &Config) -> Result<()> {
+        self.overwrite.apply_config(config);
+
+        self.template = match self.preset.get(config)? {
+            Some((_, preset)) => preset.args.clone(),
+            None => mem::take(&mut self.args),
+        };
+
+        ensure!(
+            self.template.iter().any(|arg| arg.contains("{input}")),
+            "the FFmpeg arguments must contain an `{{input}}` placeholder"
+        );
+
+        ffmpeg::check_options(
+            &[&self.template[..], &self.args].concat(),
+            self.overwrite.enabled(),
+        )
+    }
+
+    /// Create the FFmpeg arguments for a single job of the batch.
+    pub fn job_args(&self, input: &Path, output: &Path) -> Result<Vec<String>> {
+        Ok(config::fill_template(
+            &self.template,
+            input.to_str().context("input path is not valid UTF-8")?,
+            Some(output.to_str().context("output path is not valid UTF-8")?),
+            self.args.clone(),
+        ))
+    }
+}
+
+/// Configuration file, shared by all commands that encode.
+#[derive(Args)]
+pub struct ConfigArgs {
+    /// Location of the configuration file, instead of the default one in the user's config
+    /// directory.
+    #[arg(id = "config", long = "config", value_name = "CONFIG", value_hint = ValueHint::FilePath)]
+    pub path: Option<PathBuf>,
+}
+
+impl ConfigArgs {
+    pub fn load(&self) -> Result<Config> {
+        config::load(self.path.as_deref())
+    }
+}
+
+/// Single preset to take the FFmpeg arguments from, shared by the commands that encode with one
+/// set of arguments.
+#[derive(Args)]
+pub struct PresetArgs {
+    /// Named preset from the configuration file, that provides the FFmpeg arguments. Any extra
+    /// arguments are inserted right before the output file.
+    #[arg(id = "preset", short = 'p', long = "preset", value_name = "PRESET")]
+    pub name: Option<String>,
+}
+
+impl PresetArgs {
+    /// Look up the selected preset, if any, together with its name.
+    fn get<'a>(&'a self, config: &'a Config) -> Result<Option<(&'a str, &'a Preset)>> {
+        self.name
+            .as_deref()
+            .map(|name| {
+                let preset = config
+                    .presets
+                    .get(name)
+                    .with_context(|| format!("preset `{name}` doesn't exist"))?;
+                Ok((name, preset))
+            })
+            .transpose()
+    }
+}
+
+/// Overwriting of existing output files, shared by all commands that write to a chosen output.
+#[derive(Args)]
+pub struct OverwriteArgs {
+    /// Overwrite the output if it already exists.
+    #[arg(short = 'y', long, overrides_with = "no_overwrite")]
+    overwrite: bool,
+    /// Don't overwrite the output, even if the configuration enables it.
+    #[arg(long, overrides_with = "overwrite")]
+    no_overwrite: bool,
+}
+
+impl OverwriteArgs {
+    fn apply_config(&mut self, config: &Config) {
+        self.overwrite = flag(self.overwrite, self.no_overwrite, config.defaults.overwrite);
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.overwrite
+    }
+}
+
+/// Handling of an encoding's output, shared by single and batch runs.
+#[derive(Args)]
+pub struct OutputArgs {
+    #[command(flatten)]
+    pub guard: GuardArgs,
+    #[command(flatten)]
+    pub keep: KeepArgs,
+    #[command(flatten)]
+    pub verify: VerifyArgs,
+    #[command(flatten)]
+    pub passes: PassArgs,
+}
+
+/// Size guard, that stops encodings projected to end up larger than allowed.
+#[derive(Args)]
+pub struct GuardArgs {
+    /// Abort the encoding once the projected output size exceeds this ratio of the input size,
+    /// for example `1.0` to stop any encoding that would end up larger than its input.
+    #[arg(long, value_parser = positive)]
+    size_guard: Option<f64>,
+    /// Fraction of the input that must be processed, before the size guard is checked.
+    #[arg(long, default_value_t = 0.1, value_parser = fraction)]
+    size_guard_after: f64,
+    /// Only warn instead of aborting when the size guard triggers.
+    #[arg(long)]
+    size_guard_warn: bool,
+}
+
+impl GuardArgs {
+    pub fn size_guard(&self) -> Option<SizeGuard> {
+        self.size_guard.map(|ratio| SizeGuard {
+            ratio,
+            after: self.size_guard_after,
+            abort: !self.size_guard_warn,
+        })
+    }
+}
+
+/// Comparison of input and output after encoding, to only keep the smaller one.
+#[derive(Args)]
+pub struct KeepArgs {
+    /// Compare the sizes after encoding and only keep the smaller file. A larger output is
+    /// deleted, while a smaller one either replaces the input or moves the input into the trash.
+    #[arg(long, value_enum)]
+    keep_smaller: Option<KeepMode>,
+    /// Directory to move inputs into with `--keep-smaller trash`, instead of the default one in
+    /// the user's data directory.
+    #[arg(long, requires = "keep_smaller", value_hint = ValueHint::DirPath)]
+    trash_dir: Option<PathBuf>,
+    /// Only report which file would be kept, without deleting or moving anything.
+    #[arg(long, requires = "keep_smaller")]
+    keep_dry_run: bool,
+}
+
+impl KeepArgs {
+    pub fn keep_smaller(&self) -> Result<Option<KeepSmaller>> {
+        self.keep_smaller
+            .map(|mode| {
+                Ok(KeepSmaller {
+                    mode,
+                    trash_dir: match &self.trash_dir {
+                        Some(dir) => dir.clone(),
+                        None => KeepSmaller::default_trash_dir()?,
+                    },
+                    dry_run: self.keep_dry_run,
+                })
+            })
+            .transpose()
+    }
+}
+
+/// Verification of the output against the input after encoding.
+#[derive(Args)]
+pub struct VerifyArgs {
+    /// Probe the output after encoding, and check it against the input. A failed verification
+    /// counts as a failed encoding, and skips the keep-smaller step.
+    #[arg(long)]
+    verify: bool,
+    /// Maximum allowed difference between the input and output duration, in seconds.
+    #[arg(long, default_value_t = 1.0, value_parser = positive)]
+    verify_tolerance: f64,
+    /// Expected amount of streams in the output, instead of the same amount as the input.
+    #[arg(long, requires = "verify")]
+    verify_streams: Option<u32>,
+}
+
+impl VerifyArgs {
+    pub fn verify(&self) -> Option<Verify> {
+        self.verify.then_some(Verify {
+            tolerance: self.verify_tolerance,
+            streams: self.verify_streams,
+        })
+    }
+}
+
+/// Analysis passes over the finished output.
+#[derive(Args)]
+pub struct PassArgs {
+    /// Fully decode the output after encoding, to find any corrupt frames. Any decode error
+    /// counts as a failed encoding, and skips the keep-smaller step.
+    #[arg(long)]
+    pub check_integrity: bool,
+    /// Compare the output with the input after encoding, measuring SSIM, PSNR and VMAF (if FFmpeg
+    /// was built with it) for each frame. The scores are shown in the statistics.
+    #[arg(long)]
+    pub quality: bool,
+}
+
+#[derive(Args)]
+pub struct TrialArgs {
+    /// Input media file to take the samples from.
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub input: PathBuf,
+    /// Amount of samples, spread evenly across the input.
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u16).range(1..))]
+    pub samples: u16,
+    /// Length of each sample in seconds.
+    #[arg(long, default_value_t = 10.0, value_parser = positive)]
+    pub sample_length: f64,
+    /// File extension of the encoded samples, instead of the input's one.
+    #[arg(short, long)]
+    pub extension: Option<String>,
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    /// Named preset from the configuration file, that provides the FFmpeg arguments. Can be given
+    /// multiple times to compare several presets side by side, in which case any explicit
+    /// arguments are added to each of them.
+    #[arg(short, long)]
+    pub preset: Vec<String>,
+    /// Argument template to pass to FFmpeg, with `{input}` and `{output}` placeholders for each
+    /// sample. If presets are used, these are extra arguments instead.
+    #[arg(raw = true)]
+    pub args: Vec<String>,
+    /// Named templates to try, resolved from either the presets or the explicit arguments.
+    #[arg(skip)]
+    templates: Vec<(String, Vec<String>)>,
+}
+
+impl TrialArgs {
+    /// Merge the configuration into these arguments, resolving the argument templates from the
+    /// presets if any are selected.
+    pub fn apply_config(&mut self, config: &Config) -> Result<()> {
+        self.templates = if self.preset.is_empty() {
+            vec![("args".to_owned(), mem::take(&mut self.args))]
+        } else {
+            self.preset
+                .iter()
+                .map(|name| {
+                    let preset = config
+                        .presets
+                        .get(name)
+                        .with_context(|| format!("preset `{name}` doesn't exist"))?;
+                    Ok((name.clone(), preset.args.clone()))
+                })
+                .collect::<Result<_>>()?
+        };
+
+        ensure!(
+            self.templates
+                .iter()
+                .all(|(_, template)| template.iter().any(|arg| arg.contains("{input}"))),
+            "the FFmpeg arguments must contain an `{{input}}` placeholder"
+        );
+
+        // Samples are written into a temporary directory, and always overwritten.
+        self.templates.iter().try_for_each(|(_, template)| {
+            ffmpeg::check_options(&[&template[..], &self.args].concat(), true)
+        })
+    }
+
+    /// Names of the argument sets to try.
+    pub fn sets(&self) -> impl Iterator<Item = &str> {
+        self.templates.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Create the FFmpeg arguments for encoding a single sample with one of the argument sets.
+    pub fn sample_args(&self, set: usize, input: &Path, output: &Path) -> Result<Vec<String>> {
+        Ok(config::fill_template(
+            &self.templates[set].1,
+            input.to_str().context("sample path is not valid UTF-8")?,
+            Some(output.to_str().context("output path is not valid UTF-8")?),
+            self.args.clone(),
+        ))
+    }
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("target").required(true).args(["target_vmaf", "max_size"])))]
+pub struct CrfSearchArgs {
+    /// Input media file to encode.
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub input: PathBuf,
+    /// Output media file of the final encoding, filled into the `{output}` placeholder.
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub output: PathBuf,
+    /// Look for the highest CRF value, that still reaches this mean VMAF score over all samples.
+    #[arg(long, value_parser = positive)]
+    pub target_vmaf: Option<f64>,
+    /// Look for the lowest CRF value, that keeps the projected output below this size. Takes
+    /// plain bytes or a `K`, `M` or `G` suffix, like `700M`.
+    #[arg(long, value_parser = size)]
+    pub max_size: Option<u64>,
+    /// Lowest CRF value to consider.
+    #[arg(long, default_value_t = 15, value_parser = clap::value_parser!(u8).range(..=63))]
+    pub min_crf: u8,
+    /// Highest CRF value to consider.
+    #[arg(long, default_value_t = 45, value_parser = clap::value_parser!(u8).range(..=63))]
+    pub max_crf: u8,
+    /// Amount of samples, spread evenly across the input.
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u16).range(1..))]
+    pub samples: u16,
+    /// Length of each sample in seconds.
+    #[arg(long, default_value_t = 10.0, value_parser = positive)]
+    pub sample_length: f64,
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    #[command(flatten)]
+    pub preset: PresetArgs,
+    #[command(flatten)]
+    pub overwrite: OverwriteArgs,
+    /// Show the statistics screen after the final encoding is done.
+    #[arg(long, overrides_with = "no_show_stats")]
+    pub show_stats: bool,
+    /// Don't show the statistics screen, even if the configuration enables it.
+    #[arg(long, overrides_with = "show_stats")]
+    no_show_stats: bool,
+    /// Save the statistics of the final encoding to a file, together with all attempts.
+    #[arg(long, overrides_with = "no_save_stats")]
+    pub save_stats: bool,
+    /// Don't save the statistics, even if the configuration enables it.
+    #[arg(long, overrides_with = "save_stats")]
+    no_save_stats: bool,
+    /// Argument template to pass to FFmpeg, with `{input}`, `{output}` and `{crf}` placeholders.
+    /// If a preset is used, these are extra arguments instead.
+    #[arg(raw = true)]
+    pub args: Vec<String>,
+    /// Template, resolved from either the preset or the explicit arguments.
+    #[arg(skip)]
+    template: Vec<String>,
+}
+
+impl CrfSearchArgs {
+    /// Goal of the search, from whichever target was given.
+    pub fn target(&self) -> Target {
+        match (self.target_vmaf, self.max_size) {
+            (Some(vmaf), _) => Target::Vmaf(vmaf),
+            (None, Some(size)) => Target::Size(size),
+            (None, None) => unreachable!("clap requires one of the targets"),
+        }
+    }
+
+    /// Merge the configuration into these arguments, resolving the argument template from the
+    /// preset if one is selected.
+    pub fn apply_config(&mut self, config: &Config) -> Result<()> {
+        self.overwrite.apply_config(config);
+        self.show_stats = flag(
+            self.show_stats,
+            self.no_show_stats,
+            config.defaults.show_stats,
+        );
+        self.save_stats = flag(
+            self.save_stats,
+            self.no_save_stats,
+            config.defaults.save_stats,
+        );
+
+        ensure!(
+            self.min_crf <= self.max_crf,
+            "the minimum CRF must not be larger than the maximum"
+        );
+
+        self.template = match self.preset.get(config)? {
+            Some((_, preset)) => preset.args.clone(),
+            None => mem::take(&mut self.args),
+        };
+
+        ensure!(
+            self.template.iter().any(|arg| arg.contains("{input}")),
+            "the FFmpeg arguments must contain an `{{input}}` placeholder"
+        );
+        ensure!(
+            self.template.iter().any(|arg| arg.contains("{crf}")),
+            "the FFmpeg arguments must contain a `{{crf}}` placeholder"
+        );
+
+        // The samples are always overwritten, so this covers the final encoding as well.
+        ffmpeg::check_options(&[&self.template[..], &self.args].concat(), true)
+    }
+
+    /// Create the FFmpeg arguments for encoding the input into the output with the given CRF.
+    pub fn encode_args(&self, crf: u8, input: &Path, output: &Path) -> Result<Vec<String>> {
+        Ok(config::fill_template(
+            &search::fill_crf(&self.template, crf),
+            input.to_str().context("input path is not valid UTF-8")?,
+            Some(output.to_str().context("output path is not valid UTF-8")?),
+            self.args.clone(),
+        ))
+    }
+}
+
+#[derive(Subcommand)]
+pub enum QueueCommand {
+    /// Continue an interrupted batch run. Finished jobs are skipped, interrupted ones are started
+    /// again from scratch.
+    Resume(ResumeArgs),
+    /// Show the state of all jobs in the queue.
+    Show {
+        /// Location of the queue file, instead of the default one in the user's data directory.
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        queue_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+pub struct ResumeArgs {
+    /// Location of the queue file, instead of the default one in the user's data directory.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub queue_file: Option<PathBuf>,
+    /// Run failed jobs again as well.
+    #[arg(long)]
+    pub retry_failed: bool,
+}
+
+/// Settings for the live progress view, allowing to trade update frequency against load.
+#[derive(Args)]
+pub struct LiveArgs {
+    /// Time window in seconds, that is kept for the charts in the live progress view [default:
+    /// 500].
+    #[arg(long, global = true, value_parser = positive)]
+    pub history: Option<f64>,
+    /// Interval in seconds at which FFmpeg reports its progress [default: 0.5].
+    #[arg(long, global = true, value_parser = positive)]
+    pub stats_period: Option<f64>,
+    /// Maximum amount of screen redraws per second, in between progress reports [default: 4].
+    #[arg(long, global = true, value_parser = positive)]
+    pub redraw_rate: Option<f64>,
+    /// Source of the overall progress. Stream copies report unreliable output times, so `auto`
+    /// tracks how much of the input file was read for them instead.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub progress_source: ProgressSource,
+    /// Initial smoothing of noisy values in the charts, either `off`, `sma:<window>` or
+    /// `ema:<window>`. Can be cycled with the `s` key while running.
+    #[arg(long, global = true, default_value_t)]
+    pub smoothing: Smoothing,
+}
+
+impl LiveArgs {
+    /// Upper limit for the samples kept in the live view, so a long history with a short stats
+    /// period can't allocate huge buffers.
+    const MAX_SAMPLES: usize = 10_000;
+
+    /// Fill in the settings that weren't given on the command line from the configuration.
+    pub fn apply_config(&mut self, config: &Config) -> Result<()> {
+        let defaults = &config.defaults;
+        for value in [
+            defaults.history,
+            defaults.stats_period,
+            defaults.redraw_rate,
+        ] {
+            ensure!(
+                value.is_none_or(|v| v > 0.0),
+                "live view settings in the configuration must be positive"
+            );
+        }
+
+        self.history = self.history.or(defaults.history);
+        self.stats_period = self.stats_period.or(defaults.stats_period);
+        self.redraw_rate = self.redraw_rate.or(defaults.redraw_rate);
+
+        Ok(())
+    }
+
+    pub fn history(&self) -> f64 {
+        self.history.unwrap_or(500.0)
+    }
+
+    pub fn stats_period(&self) -> f64 {
+        self.stats_period.unwrap_or(0.5)
+    }
+
+    pub fn redraw_rate(&self) -> f64 {
+        self.redraw_rate.unwrap_or(4.0)
+    }
+
+    /// Amount of samples that fit into the history window.
+    pub fn samples(&self) -> usize {
+        ((self.history() / self.stats_period()).ceil() as usize).min(Self::MAX_SAMPLES)
+    }
+
+    /// Time to wait for user input between screen redraws.
+    pub fn redraw_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.redraw_rate())
+    }
+}
+
+/// Locations of the FFmpeg binaries, for custom builds with extra libraries or hardware codecs.
+#[derive(Args)]
+pub struct ToolArgs {
+    /// Path of the `ffmpeg` binary, instead of searching for it in the `PATH`.
+    #[arg(long, global = true, env = "FFPROG_FFMPEG", value_hint = ValueHint::ExecutablePath)]
+    pub ffmpeg: Option<PathBuf>,
+    /// Path of the `ffprobe` binary, instead of searching for it in the `PATH`.
+    #[arg(long, global = true, env = "FFPROG_FFPROBE", value_hint = ValueHint::ExecutablePath)]
+    pub ffprobe: Option<PathBuf>,
+}
+
+impl ToolArgs {
+    /// Combine the binaries from the command line with the configured ones, with the former taking
+    /// precedence.
+    pub fn resolve(&self, config: &Config) -> Tools {
+        let defaults = Tools::default();
+        Tools {
+            ffmpeg: (self.ffmpeg.as_ref())
+                .or(config.binaries.ffmpeg.as_ref())
+                .map_or(defaults.ffmpeg, Clone::clone),
+            ffprobe: (self.ffprobe.as_ref())
+                .or(config.binaries.ffprobe.as_ref())
+                .map_or(defaults.ffprobe, Clone::clone),
+        }
+    }
+}
+
+/// Notifications about finished encodings, for long runs that aren't watched all the time.
+#[derive(Args)]
+pub struct NotifyArgs {
+    /// Notify when the encoding completes, fails or is cancelled, with the terminal bell and an
+    /// OSC 9 message (`terminal`) or a desktop notification (`desktop`). Both can be given,
+    /// separated by a comma.
+    #[arg(long, global = true, value_enum, value_delimiter = ',')]
+    pub notify: Vec<Channel>,
+}
+
+/// Resolve a flag with a `--no-` counterpart, falling back to the configured default if neither
+/// was given.
+fn flag(on: bool, off: bool, default: bool) -> bool {
+    on || (!off && default)
+}
+
+fn fraction(value: &str) -> Result<f64> {
+    let value = value.parse::<f64>()?;
+    ensure!(
+        (0.0..=1.0).contains(&value),
+        "value must be between 0 and 1"
+    );
+    Ok(value)
+}
+
+/// Parse a file size in bytes, with an optional `K`, `M` or `G` suffix.
+fn size(value: &str) -> Result<u64> {
+    let (number, factor) = match value.trim().to_ascii_uppercase() {
+        v if v.ends_with('K') => (v[..v.len() - 1].to_owned(), 1_000.0),
+        v if v.ends_with('M') => (v[..v.len() - 1].to_owned(), 1_000_000.0),
+        v if v.ends_with('G') => (v[..v.len() - 1].to_owned(), 1_000_000_000.0),
+        v => (v, 1.0),
+    };
+    let number = number.parse::<f64>()?;
+    ensure!(
+        number.is_finite() && number > 0.0,
+        "size must be a positive number"
+    );
+    Ok((number * factor) as u64)
+}
+
+fn positive(value: &str) -> Result<f64> {
+    let value = value.parse::<f64>()?;
+    ensure!(
+        value.is_finite() && value > 0.0,
+        "value must be a positive number"
+    );
+    Ok(value)
+}
+
 /// Generate shell completions, written to the standard output.
 pub fn completions(shell: Shell) {
     clap_complete::generate(
@@ -113,10 +840,100 @@ pub fn manpages(dir: &Path) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::Cli;
+    use crate::config::Config;
 
     #[test]
     fn verify_cli() {
         use clap::CommandFactory;
         Cli::command().debug_assert();
     }
+
+    #[test]
+    fn parse_run_args() {
+        let cli = <Cli as clap::Parser>::parse_from(["ffprog", "-i", "in.mkv", "--", "out.mkv"]);
+        assert!(cli.run.is_some());
+    }
+
+    #[test]
+    fn batch_template() {
+        let cli = <Cli as clap::Parser>::parse_from([
+            "ffprog", "batch", "a.mkv", "-o", "out", "--", "-i", "{input}", "-an",
+        ]);
+        let mut args = match cli.cmd {
+            Some(super::Command::Batch(args)) => args,
+            _ => panic!("expected batch command"),
+        };
+        args.apply_config(&Config::default()).unwrap();
+
+        assert_eq!(
+            ["-i", "a.mkv", "-an", "out/a.mkv"].as_slice(),
+            args.job_args("a.mkv".as_ref(), "out/a.mkv".as_ref())
+                .unwrap()
+                .as_slice()
+        );
+    }
+
+    #[test]
+    fn parse_size() {
+        assert_eq!(1500, super::size("1500").unwrap());
+        assert_eq!(700_000_000, super::size("700M").unwrap());
+        assert_eq!(4_500_000_000, super::size("4.5g").unwrap());
+        assert!(super::size("-1K").is_err());
+    }
+
+    #[test]
+    fn merge_preset() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [defaults]
+            show_stats = true
+
+            [presets.small]
+            args = ["-i", "{input}", "-crf", "28", "{output}"]
+            "#,
+        )
+        .unwrap();
+
+        let cli = <Cli as clap::Parser>::parse_from([
+            "ffprog", "-p", "small", "-i", "in.mkv", "out.mkv", "--", "-an",
+        ]);
+        let mut args = cli.run.unwrap();
+        args.apply_config(&config).unwrap();
+
+        assert!(args.show_stats);
+        assert!(!args.save_stats);
+        assert_eq!(
+            ["-i", "in.mkv", "-crf", "28", "-an", "out.mkv"].as_slice(),
+            args.args.as_slice()
+        );
+    }
+
+    #[test]
+    fn disable_configured_flags() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [defaults]
+            overwrite = true
+            show_stats = true
+            "#,
+        )
+        .unwrap();
+
+        let cli = <Cli as clap::Parser>::parse_from([
+            "ffprog",
+            "-i",
+            "in.mkv",
+            "--no-overwrite",
+            "--save-stats",
+            "--no-save-stats",
+            "--",
+            "out.mkv",
+        ]);
+        let mut args = cli.run.unwrap();
+        args.apply_config(&config).unwrap();
+
+        assert!(!args.overwrite.enabled());
+        assert!(args.show_stats);
+        assert!(!args.save_stats);
+    }
 }
@@ -0,0 +1,225 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::hooks::Hook;
+
+/// User configuration, loaded from a TOML file.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Default values for the flags of a run.
+    pub defaults: Defaults,
+    /// Named sets of FFmpeg arguments.
+    pub presets: BTreeMap<String, Preset>,
+    /// Locations of the FFmpeg binaries, instead of searching for them in the `PATH`.
+    pub binaries: Binaries,
+    /// Commands and webhooks to run on the start, progress and end of encodings.
+    pub hooks: Vec<Hook>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Defaults {
+    pub overwrite: bool,
+    pub show_stats: bool,
+    pub save_stats: bool,
+    /// Settings for the live progress view, see the matching command line flags.
+    pub history: Option<f64>,
+    pub stats_period: Option<f64>,
+    pub redraw_rate: Option<f64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Binaries {
+    pub ffmpeg: Option<PathBuf>,
+    pub ffprobe: Option<PathBuf>,
+}
+
+/// Arguments to pass to FFmpeg, with the `{input}` and `{output}` placeholders being replaced with
+/// the actual file paths.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    pub args: Vec<String>,
+}
+
+/// Default location of the configuration file, inside the user's config directory.
+pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join("config.toml"))
+}
+
+/// Load the configuration from the given path, or the default location if not set. A missing
+/// file in the default location is not an error, and results in the default configuration.
+pub fn load(path: Option<&Path>) -> Result<Config> {
+    let (path, required) = match path {
+        Some(path) => (path.to_owned(), true),
+        None => match default_path() {
+            Some(path) => (path, false),
+            None => return Ok(Config::default()),
+        },
+    };
+
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound && !required => return Ok(Config::default()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("failed reading `{}`", path.display()));
+        }
+    };
+
+    toml::from_str(&content).with_context(|| format!("invalid config file `{}`", path.display()))
+}
+
+/// Whether the template contains the `{output}` placeholder.
+pub fn has_output(template: &[String]) -> bool {
+    template.iter().any(|arg| arg.contains("{output}"))
+}
+
+/// Fill the placeholders of an argument template with the input and output file. The extra
+/// arguments are inserted right before the output file, and the output is appended if the template
+/// has no placeholder for it.
+pub fn fill_template(
+    template: &[String],
+    input: &str,
+    output: Option<&str>,
+    extra: Vec<String>,
+) -> Vec<String> {
+    let mut extra = Some(extra);
+    let mut args = Vec::with_capacity(template.len() + 1);
+
+    for arg in template {
+        if arg.contains("{output}") {
+            args.extend(extra.take().into_iter().flatten());
+        }
+        args.push(fill(arg, input, output.unwrap_or_default()));
+    }
+
+    args.extend(extra.into_iter().flatten());
+
+    if let Some(output) = output.filter(|_| !has_output(template)) {
+        args.push(output.to_owned());
+    }
+
+    args
+}
+
+/// Replace the placeholders of a single argument in one pass, so a file name that contains the
+/// text of a placeholder is kept as is.
+fn fill(arg: &str, input: &str, output: &str) -> String {
+    let mut filled = String::with_capacity(arg.len());
+    let mut rest = arg;
+
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(after) = rest.strip_prefix("{input}") {
+            filled.push_str(input);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("{output}") {
+            filled.push_str(output);
+            rest = after;
+        } else {
+            filled.push('{');
+            rest = &rest[1..];
+        }
+    }
+
+    filled.push_str(rest);
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    #[test]
+    fn parse_config() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [defaults]
+            save_stats = true
+            stats_period = 1.0
+
+            [presets.hevc-small]
+            args = ["-i", "{input}", "-c:v", "libx265", "-crf", "28", "{output}"]
+
+            [binaries]
+            ffmpeg = "/opt/ffmpeg/bin/ffmpeg"
+
+            [[hooks]]
+            events = ["start", "milestone"]
+            command = "echo $FFPROG_INPUT"
+            milestone = 10
+
+            [[hooks]]
+            events = ["finish", "failure", "cancel"]
+            url = "http://localhost:8080/ffprog"
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.defaults.save_stats);
+        assert!(!config.defaults.show_stats);
+        assert_eq!(Some(1.0), config.defaults.stats_period);
+        assert_eq!(None, config.defaults.history);
+        assert_eq!(7, config.presets["hevc-small"].args.len());
+        assert_eq!(
+            Some("/opt/ffmpeg/bin/ffmpeg".as_ref()),
+            config.binaries.ffmpeg.as_deref()
+        );
+        assert_eq!(None, config.binaries.ffprobe);
+        assert_eq!(2, config.hooks.len());
+        assert_eq!(10, config.hooks[0].milestone);
+        assert_eq!(25, config.hooks[1].milestone);
+    }
+
+    #[test]
+    fn fill_without_output_placeholder() {
+        let template = ["-i", "{input}", "-an"].map(String::from);
+        let args = super::fill_template(&template, "a.mkv", Some("b.mkv"), vec!["-sn".to_owned()]);
+
+        assert_eq!(["-i", "a.mkv", "-an", "-sn", "b.mkv"].as_slice(), args);
+    }
+
+    #[test]
+    fn fill_placeholders_once() {
+        let template = [
+            "-i",
+            "{input}",
+            "-metadata",
+            "title={input} ({x})",
+            "{output}",
+        ]
+        .map(String::from);
+        let args = super::fill_template(&template, "{output}.mkv", Some("b.mkv"), Vec::new());
+
+        assert_eq!(
+            [
+                "-i",
+                "{output}.mkv",
+                "-metadata",
+                "title={output}.mkv ({x})",
+                "b.mkv"
+            ]
+            .as_slice(),
+            args
+        );
+    }
+
+    #[test]
+    fn parse_empty() {
+        let config = toml::from_str::<Config>("").unwrap();
+
+        assert!(!config.defaults.overwrite);
+        assert!(config.presets.is_empty());
+    }
+}
@@ -0,0 +1,111 @@
+/// Aggregated view of a data series, reduced to a fixed amount of buckets. Each bucket contributes
+/// one point to each of the lines, so spikes stay visible through the min/max envelopes while the
+/// mean shows the general trend.
+#[derive(Default)]
+pub struct Buckets {
+    pub min: Vec<(f64, f64)>,
+    pub max: Vec<(f64, f64)>,
+    pub mean: Vec<(f64, f64)>,
+}
+
+/// Reduce the data to at most `count` buckets of consecutive points. If the data already fits, it
+/// is returned as mean line only, without any envelopes.
+pub fn downsample(data: &[(f64, f64)], count: usize) -> Buckets {
+    if count == 0 || data.len() <= count {
+        return Buckets {
+            mean: data.to_vec(),
+            ..Buckets::default()
+        };
+    }
+
+    let size = data.len().div_ceil(count);
+    let mut buckets = Buckets {
+        min: Vec::with_capacity(count),
+        max: Vec::with_capacity(count),
+        mean: Vec::with_capacity(count),
+    };
+
+    for chunk in data.chunks(size) {
+        let mut x_sum = 0.0;
+        let mut y_sum = 0.0;
+        let mut y_min = f64::MAX;
+        let mut y_max = f64::MIN;
+
+        for (x, y) in chunk.iter().copied() {
+            x_sum += x;
+            y_sum += y;
+            y_min = y_min.min(y);
+            y_max = y_max.max(y);
+        }
+
+        let len = chunk.len() as f64;
+        let x = x_sum / len;
+
+        buckets.min.push((x, y_min));
+        buckets.max.push((x, y_max));
+        buckets.mean.push((x, y_sum / len));
+    }
+
+    buckets
+}
+
+/// Full history of a data series, that lazily creates a downsampled version of itself, fitting
+/// the width it is rendered into. The result is cached until the width changes.
+pub struct Series {
+    data: Vec<(f64, f64)>,
+    width: Option<u16>,
+    buckets: Buckets,
+}
+
+impl Series {
+    pub fn new(data: Vec<(f64, f64)>) -> Self {
+        Self {
+            data,
+            width: None,
+            buckets: Buckets::default(),
+        }
+    }
+
+    pub fn data(&self) -> &[(f64, f64)] {
+        &self.data
+    }
+
+    pub fn get(&mut self, width: u16) -> &Buckets {
+        if self.width != Some(width) {
+            // Braille markers give two dots per terminal cell horizontally.
+            self.buckets = downsample(&self.data, width as usize * 2);
+            self.width = Some(width);
+        }
+
+        &self.buckets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_data_is_untouched() {
+        let data = vec![(0.0, 1.0), (1.0, 2.0), (2.0, 3.0)];
+        let buckets = downsample(&data, 5);
+
+        assert_eq!(data, buckets.mean);
+        assert!(buckets.min.is_empty());
+        assert!(buckets.max.is_empty());
+    }
+
+    #[test]
+    fn spikes_remain_in_envelope() {
+        let data = (0..1000)
+            .map(|i| (i as f64, if i == 500 { 100.0 } else { 1.0 }))
+            .collect::<Vec<_>>();
+        let buckets = downsample(&data, 10);
+
+        assert_eq!(10, buckets.mean.len());
+        assert_eq!(100.0, buckets.max[5].1);
+        assert_eq!(1.0, buckets.min[5].1);
+        assert!((buckets.mean[5].1 - 1.99).abs() < 1e-9);
+        assert_eq!(49.5, buckets.mean[0].0);
+    }
+}
@@ -1,14 +1,145 @@
 use std::{
+    collections::BTreeMap,
     io::{BufRead, BufReader},
-    process::{Child, ChildStdout, Command, Stdio},
+    mem,
+    path::{Path, PathBuf},
+    process::{Child, ChildStdout, Stdio},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
 };
 
-use anyhow::{ensure, Context, Result};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use clap::ValueEnum;
 use time::Duration;
 
+use crate::{
+    priority::{self, Priority},
+    tools,
+    usage::{self, Usage},
+};
+
 pub struct ProgressIter {
     child: Option<Child>,
     reader: BufReader<ChildStdout>,
+    log: Arc<Mutex<Vec<String>>>,
+    log_reader: Option<JoinHandle<()>>,
+    /// Warnings that weren't taken yet, only kept for the first [`Self::MAX_WARNINGS`] of them.
+    warnings: Vec<String>,
+    /// Total amount of progress values that were ignored.
+    ignored: usize,
+    /// Input file of an encoding, to track how much of it was read.
+    input: Option<PathBuf>,
+    /// Whether all streams are only copied, making the reported times unreliable.
+    stream_copy: bool,
+}
+
+impl ProgressIter {
+    /// Maximum amount of warnings kept, as a broken output can make every single value invalid.
+    const MAX_WARNINGS: usize = 100;
+
+    fn new(mut child: Child) -> Result<Self> {
+        let stdout = child
+            .stdout
+            .take()
+            .context("failed taking stdout from ffmpeg")?;
+        let stderr = child
+            .stderr
+            .take()
+            .context("failed taking stderr from ffmpeg")?;
+
+        // The log is read in the background, so FFmpeg never blocks on a full pipe when it prints
+        // lots of messages.
+        let log = Arc::<Mutex<Vec<String>>>::default();
+        let log_reader = {
+            let log = Arc::clone(&log);
+            thread::spawn(move || {
+                for line in BufReader::new(stderr).lines() {
+                    let Ok(line) = line else { break };
+                    log.lock().expect("log is never poisoned").push(line);
+                }
+            })
+        };
+
+        Ok(Self {
+            child: Some(child),
+            reader: BufReader::new(stdout),
+            log,
+            log_reader: Some(log_reader),
+            warnings: Vec::new(),
+            ignored: 0,
+            input: None,
+            stream_copy: false,
+        })
+    }
+
+    /// Lines that FFmpeg logged so far. Complete once all progress has been read.
+    pub fn log(&self) -> Vec<String> {
+        self.log.lock().expect("log is never poisoned").clone()
+    }
+
+    /// Take the warnings about progress values that were skipped since the last call.
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        mem::take(&mut self.warnings)
+    }
+
+    /// Total amount of progress values that couldn't be parsed and were skipped.
+    pub fn ignored(&self) -> usize {
+        self.ignored
+    }
+
+    fn warn(&mut self, message: String) {
+        self.ignored += 1;
+        if self.ignored <= Self::MAX_WARNINGS {
+            self.warnings.push(message);
+        }
+    }
+
+    /// Current resource usage of the running process.
+    pub fn usage(&self) -> Option<Usage> {
+        Usage::sample(self.child.as_ref()?.id())
+    }
+
+    /// Bytes of the input file that the running process read so far, if progress is tracked by the
+    /// input with the given source.
+    pub fn input_read(&self, source: ProgressSource) -> Option<u64> {
+        let by_input = match source {
+            ProgressSource::Auto => self.stream_copy,
+            ProgressSource::Time => false,
+            ProgressSource::Input => true,
+        };
+        if !by_input {
+            return None;
+        }
+
+        usage::input_position(self.child.as_ref()?.id(), self.input.as_deref()?)
+    }
+
+    /// Process ID of FFmpeg, while it's still running.
+    pub fn id(&self) -> Option<u32> {
+        self.child.as_ref().map(Child::id)
+    }
+
+    /// Raise or lower the nice value of the running process.
+    pub fn adjust_nice(&self, delta: i32) -> Result<Priority> {
+        let pid = self.id().context("ffmpeg already finished")?;
+        priority::adjust(&[pid], delta)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let Some(mut child) = self.child.take() else {
+            return Ok(());
+        };
+
+        let status = child.wait()?;
+
+        if let Some(log_reader) = self.log_reader.take() {
+            log_reader.join().ok();
+        }
+
+        ensure!(status.success(), "{}", self.log().join("\n"));
+
+        Ok(())
+    }
 }
 
 impl Iterator for ProgressIter {
@@ -25,18 +156,15 @@ impl Iterator for ProgressIter {
 
             match self.reader.read_line(&mut buf) {
                 Ok(0) => {
-                    return match finish_process(self.child.take()) {
+                    return match self.finish() {
                         Ok(()) => None,
                         Err(e) => Some(Err(e)),
                     };
                 }
-                Ok(_) => match buf.trim().split_once('=') {
-                    Some((key, value)) => match parse_kv(&mut progress, key, value) {
-                        Ok(true) => return Some(Ok(progress)),
-                        Ok(false) => continue,
-                        Err(e) => return Some(Err(e)),
-                    },
-                    None => continue,
+                Ok(_) => match parse_line(&mut progress, &buf) {
+                    Ok(true) => return Some(Ok(progress)),
+                    Ok(false) => {}
+                    Err(e) => self.warn(e.to_string()),
                 },
                 Err(e) => return Some(Err(e.into())),
             }
@@ -48,29 +176,59 @@ impl Drop for ProgressIter {
     fn drop(&mut self) {
         if let Some(mut child) = self.child.take() {
             child.kill().ok();
+            // Reap the process, so it no longer writes to the output once the iterator is gone.
+            child.wait().ok();
         }
     }
 }
 
+/// Parse a single line of the progress output into the current block, returning whether the
+/// block is complete. Values that can't be parsed are returned as error, for the caller to record
+/// as warning and skip, so a single odd value never stops the encoding.
+fn parse_line(progress: &mut Progress, line: &str) -> Result<bool> {
+    let Some((key, value)) = line.trim().split_once('=') else {
+        return Ok(false);
+    };
+
+    parse_kv(progress, key, value)
+        .map_err(|e| anyhow!("invalid progress value `{key}={value}`: {e}"))
+}
+
 fn parse_kv(progress: &mut Progress, key: &str, value: &str) -> Result<bool> {
     let value = value.trim();
+    // Values that aren't known yet, like the bitrate before the first packet was written.
+    if value == "N/A" {
+        return Ok(key == "progress");
+    }
+
+    if let Some(stream) = parse_stream_key(key, "q") {
+        let q = value.parse::<f64>()?;
+        // Streams that aren't encoded, like copied ones, report a negative value.
+        if q >= 0.0 {
+            progress.quantizers.insert(stream, q);
+        }
+        return Ok(false);
+    }
+
     match key {
         "frame" => progress.frame = value.parse()?,
         "fps" => progress.fps = value.parse()?,
         "bitrate" => {
-            progress.bitrate = (value
-                .strip_suffix("kbits/s")
-                .unwrap_or(value)
-                .parse::<f64>()?
-                * 1000.0) as u64
+            progress.bitrate = Some(
+                (value
+                    .strip_suffix("kbits/s")
+                    .unwrap_or(value)
+                    .parse::<f64>()?
+                    * 1000.0) as u64,
+            )
         }
-        "total_size" => progress.total_size = value.parse()?,
-        "out_time_us" => progress.out_time_us = value.parse()?,
-        "out_time_ms" => progress.out_time_ms = value.parse()?,
-        "out_time" => progress.out_time = parse_time(value)?,
+        "total_size" => progress.total_size = Some(value.parse()?),
+        "out_time_us" => progress.out_time_us = Some(value.parse()?),
+        "out_time_ms" => progress.out_time_ms = Some(value.parse()?),
+        "out_time" => progress.out_time = Some(parse_time(value)?),
         "dup_frames" => progress.dup_frames = value.parse()?,
         "drop_frames" => progress.drop_frames = value.parse()?,
-        "speed" => progress.speed = value.strip_suffix('x').unwrap_or(value).parse()?,
+        "speed" => progress.speed = Some(value.strip_suffix('x').unwrap_or(value).parse()?),
         "progress" => return Ok(true),
         _ => return Ok(false),
     }
@@ -78,75 +236,435 @@ fn parse_kv(progress: &mut Progress, key: &str, value: &str) -> Result<bool> {
     Ok(false)
 }
 
-fn parse_time(value: &str) -> Result<Duration> {
-    let (hours, value) = value.split_once(':').context("hours missing")?;
-    let (minutes, value) = value.split_once(':').context("minutes missing")?;
-    let (seconds, micros) = value.split_once('.').context("seconds missing")?;
-
-    let total_seconds =
-        hours.parse::<i64>()? * 3600 + minutes.parse::<i64>()? * 60 + seconds.parse::<i64>()?;
+/// Parse a per-stream key like `stream_0_1_q` into the file and stream index.
+fn parse_stream_key(key: &str, name: &str) -> Option<(u32, u32)> {
+    let (file, stream) = key
+        .strip_prefix("stream_")?
+        .strip_suffix(name)?
+        .strip_suffix('_')?
+        .split_once('_')?;
 
-    Ok(Duration::seconds(total_seconds) + Duration::microseconds(micros.parse()?))
+    Some((file.parse().ok()?, stream.parse().ok()?))
 }
 
-fn finish_process(child: Option<Child>) -> Result<()> {
-    let child = match child {
-        Some(c) => c,
-        None => return Ok(()),
+/// Parse a timestamp in the form of `HH:MM:SS.ffffff`. It is negative for streams starting before
+/// zero, like `-00:00:01.500000`.
+fn parse_time(value: &str) -> Result<Duration> {
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value),
     };
 
-    let output = child.wait_with_output()?;
-
+    let (hours, value) = value.split_once(':').context("hours missing")?;
+    let (minutes, value) = value.split_once(':').context("minutes missing")?;
+    let (seconds, fraction) = value.split_once('.').unwrap_or((value, "0"));
     ensure!(
-        output.status.success(),
-        "{}",
-        String::from_utf8_lossy(&output.stderr)
+        fraction.bytes().all(|b| b.is_ascii_digit()),
+        "invalid fraction of a second"
     );
 
-    Ok(())
+    let total_seconds =
+        hours.parse::<i64>()? * 3600 + minutes.parse::<i64>()? * 60 + seconds.parse::<i64>()?;
+    // The fraction may have less than 6 digits, so pad it to microseconds.
+    let micros = format!("{fraction:0<6.6}").parse::<i64>()?;
+    let duration = Duration::seconds(total_seconds) + Duration::microseconds(micros);
+
+    Ok(if negative { -duration } else { duration })
 }
 
+/// Single block of FFmpeg's progress output. Values that FFmpeg reports as `N/A`, which happens at
+/// the start of a stream or with some muxers, are left empty.
 #[derive(Clone, Default)]
 pub struct Progress {
     pub frame: u64,
     pub fps: f64,
-    pub bitrate: u64,
-    pub total_size: u64,
-    pub out_time_us: u64,
-    pub out_time_ms: u64,
-    pub out_time: Duration,
+    pub bitrate: Option<u64>,
+    pub total_size: Option<u64>,
+    pub out_time_us: Option<i64>,
+    pub out_time_ms: Option<i64>,
+    pub out_time: Option<Duration>,
     pub dup_frames: u64,
     pub drop_frames: u64,
-    pub speed: f64,
+    pub speed: Option<f64>,
+    /// Quantizer of each encoded video stream, by output file and stream index.
+    pub quantizers: BTreeMap<(u32, u32), f64>,
 }
 
-pub fn spawn(args: &[String], overwrite: bool) -> Result<ProgressIter> {
-    let mut child = Command::new("ffmpeg")
+impl Progress {
+    /// Quantizer of the first encoded stream, which is usually the main video stream.
+    pub fn quantizer(&self) -> Option<f64> {
+        self.quantizers.values().next().copied()
+    }
+
+    /// Position in the output, treating unknown and negative times as the very start.
+    pub fn position(&self) -> Duration {
+        self.out_time.unwrap_or_default().max(Duration::ZERO)
+    }
+}
+
+/// Find the output file in the FFmpeg arguments, which is always the last one.
+pub fn output_path(args: &[String]) -> Option<PathBuf> {
+    args.last()
+        .filter(|arg| !arg.starts_with('-') && !arg.contains("://"))
+        .map(PathBuf::from)
+}
+
+pub fn spawn(args: &[String], overwrite: bool, stats_period: f64) -> Result<ProgressIter> {
+    let (options, args) = merge_options(args, overwrite)?;
+    let mut cmd = tools::ffmpeg();
+    priority::apply(&mut cmd).context("failed setting the priority of ffmpeg")?;
+
+    let child = cmd
         .args([
             "-progress",
             "pipe:1",
             "-nostats",
             "-nostdin",
             "-hide_banner",
-            "-stats_period",
-            "0.5",
-            "-loglevel",
-            "warning",
         ])
-        .arg(if overwrite { "-y" } else { "-n" })
-        .args(args)
+        .args(["-stats_period", &stats_period.to_string()])
+        .args(options)
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed starting ffmpeg")?;
+
+    let mut iter = ProgressIter::new(child)?;
+    iter.input = args
+        .windows(2)
+        .find(|pair| pair[0] == "-i")
+        .map(|pair| PathBuf::from(&pair[1]));
+    iter.stream_copy = is_stream_copy(&args);
+
+    Ok(iter)
+}
+
+/// Check whether all codecs in the arguments are set to `copy`, meaning the streams are only
+/// remuxed without encoding them.
+fn is_stream_copy(args: &[String]) -> bool {
+    let mut codecs = args
+        .windows(2)
+        .filter(|pair| {
+            let option = pair[0]
+                .split_once(':')
+                .map_or(pair[0].as_str(), |(option, _)| option);
+            matches!(option, "-c" | "-codec" | "-vcodec" | "-acodec" | "-scodec")
+        })
+        .map(|pair| pair[1].as_str())
+        .peekable();
+
+    codecs.peek().is_some() && codecs.all(|codec| codec == "copy")
+}
+
+/// Source of the overall progress of an encoding.
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum ProgressSource {
+    /// Use the input for stream copies, and the output time otherwise.
+    #[default]
+    Auto,
+    /// Position in the output, as reported by FFmpeg.
+    Time,
+    /// Bytes of the input file that FFmpeg read so far, compared with its size.
+    Input,
+}
+
+/// Check the user's arguments for options that conflict with the ones added by [`spawn`], so
+/// the conflict can be reported before starting FFmpeg.
+pub fn check_options(args: &[String], overwrite: bool) -> Result<()> {
+    merge_options(args, overwrite).map(drop)
+}
+
+/// Merge the global options, that are added to every encoding, with the user's arguments. Options
+/// that are added anyway are removed from the arguments, while the user's log level and `-y`/`-n`
+/// are honoured. Options that would break the progress reporting are rejected.
+///
+/// Returns the merged options and the remaining arguments.
+fn merge_options(args: &[String], overwrite: bool) -> Result<(Vec<String>, Vec<String>)> {
+    let mut loglevel = None;
+    let mut existing = None;
+    let mut rest = Vec::with_capacity(args.len());
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-progress" => bail!("`-progress` can't be used, as it's needed to read the progress"),
+            "-stats" | "-stdin" => bail!("`{arg}` can't be used, as it breaks the progress view"),
+            "-stats_period" => bail!("`-stats_period` can't be used, use `--stats-period` instead"),
+            "-nostats" | "-nostdin" | "-hide_banner" => {}
+            "-loglevel" | "-v" => {
+                loglevel = Some(
+                    iter.next()
+                        .with_context(|| format!("`{arg}` needs a value"))?,
+                );
+            }
+            "-y" | "-n" => {
+                ensure!(
+                    existing.is_none_or(|existing| existing == arg),
+                    "`-y` and `-n` can't be used together"
+                );
+                existing = Some(arg);
+            }
+            _ => rest.push(arg.clone()),
+        }
+    }
+
+    ensure!(
+        !overwrite || existing.is_none_or(|existing| existing == "-y"),
+        "`-n` can't be used, as the output must be overwritten"
+    );
+
+    let options = vec![
+        "-loglevel".to_owned(),
+        loglevel.map_or("warning", String::as_str).to_owned(),
+        match existing {
+            Some(existing) => existing.clone(),
+            None if overwrite => "-y".to_owned(),
+            None => "-n".to_owned(),
+        },
+    ];
+
+    Ok((options, rest))
+}
+
+/// Decode the whole file without writing anything, to find corrupt parts of it. Only errors are
+/// logged, so each line of the log is a decode error.
+pub fn spawn_check(input: &Path, stats_period: f64) -> Result<ProgressIter> {
+    spawn_analysis(&[input], None, None, stats_period)
+}
+
+/// Decode the given files without writing anything, optionally passing them through a filter
+/// graph. The process runs in the given directory, so filters can write files into it.
+pub fn spawn_analysis(
+    inputs: &[&Path],
+    filter: Option<&str>,
+    dir: Option<&Path>,
+    stats_period: f64,
+) -> Result<ProgressIter> {
+    let mut cmd = tools::ffmpeg();
+    cmd.args([
+        "-progress",
+        "pipe:1",
+        "-nostats",
+        "-nostdin",
+        "-hide_banner",
+        "-loglevel",
+        "error",
+    ])
+    .args(["-stats_period", &stats_period.to_string()]);
+    priority::apply(&mut cmd).context("failed setting the priority of ffmpeg")?;
+
+    for input in inputs {
+        cmd.arg("-i").arg(input);
+    }
+    if let Some(filter) = filter {
+        cmd.args(["-lavfi", filter]);
+    }
+    if let Some(dir) = dir {
+        cmd.current_dir(dir);
+    }
+
+    let child = cmd
+        .args(["-f", "null", "-"])
         .stdin(Stdio::null())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
-        .spawn()?;
+        .spawn()
+        .context("failed starting ffmpeg")?;
+
+    ProgressIter::new(child)
+}
+
+/// Check whether FFmpeg was built with the given filter.
+pub fn has_filter(name: &str) -> bool {
+    let Ok(output) = tools::ffmpeg().args(["-hide_banner", "-filters"]).output() else {
+        return false;
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .any(|line| line.split_whitespace().nth(1) == Some(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Duration;
+
+    use super::Progress;
+
+    #[test]
+    fn merge_global_options() {
+        let args = |args: &[&str]| args.iter().map(|&arg| arg.to_owned()).collect::<Vec<_>>();
+        let input = args(&["-i", "in.mkv", "-c:v", "libx265", "out.mkv"]);
 
-    let stdout = child
-        .stdout
-        .take()
-        .context("failed taking stdout from ffmpeg")?;
+        assert_eq!(
+            (args(&["-loglevel", "warning", "-n"]), input.clone()),
+            super::merge_options(&input, false).unwrap()
+        );
 
-    Ok(ProgressIter {
-        child: Some(child),
-        reader: BufReader::new(stdout),
-    })
+        let (options, rest) = super::merge_options(
+            &args(&[
+                "-hide_banner",
+                "-v",
+                "info",
+                "-y",
+                "-nostdin",
+                "-i",
+                "in.mkv",
+                "-c:v",
+                "libx265",
+                "out.mkv",
+            ]),
+            false,
+        )
+        .unwrap();
+        assert_eq!(args(&["-loglevel", "info", "-y"]), options);
+        assert_eq!(input, rest);
+
+        for conflict in [
+            &["-progress", "out.txt"][..],
+            &["-stats"],
+            &["-stats_period", "1"],
+            &["-y", "-n"],
+            &["-loglevel"],
+        ] {
+            assert!(super::merge_options(&args(conflict), false).is_err());
+        }
+        assert!(super::merge_options(&args(&["-n"]), true).is_err());
+    }
+
+    #[test]
+    fn detect_stream_copy() {
+        let args = |args: &[&str]| args.iter().map(|&arg| arg.to_owned()).collect::<Vec<_>>();
+
+        for (expect, input) in [
+            (true, &["-i", "in.mkv", "-c", "copy", "out.mp4"][..]),
+            (
+                true,
+                &["-i", "in.mkv", "-c:v", "copy", "-acodec", "copy", "out.mp4"],
+            ),
+            (
+                false,
+                &["-i", "in.mkv", "-c:v", "copy", "-c:a", "aac", "out.mp4"],
+            ),
+            (false, &["-i", "in.mkv", "-c:v", "libx265", "out.mkv"]),
+            (false, &["-i", "in.mkv", "out.mkv"]),
+        ] {
+            assert_eq!(expect, super::is_stream_copy(&args(input)), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn parse_stream_quantizers() {
+        let mut progress = Progress::default();
+
+        for (key, value) in [
+            ("stream_0_0_q", "28.0"),
+            ("stream_0_1_q", "-1.0"),
+            ("stream_1_2_q", "31.5"),
+        ] {
+            assert!(!super::parse_kv(&mut progress, key, value).unwrap());
+        }
+
+        assert_eq!(
+            vec![((0, 0), 28.0), ((1, 2), 31.5)],
+            progress.quantizers.into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(None, super::parse_stream_key("stream_0_q", "q"));
+    }
+
+    /// Parse a recorded progress output into its blocks and the collected warnings.
+    fn parse(corpus: &str) -> (Vec<Progress>, Vec<String>) {
+        let mut blocks = Vec::new();
+        let mut warnings = Vec::new();
+        let mut progress = Progress::default();
+
+        for line in corpus.lines() {
+            match super::parse_line(&mut progress, line) {
+                Ok(true) => blocks.push(std::mem::take(&mut progress)),
+                Ok(false) => {}
+                Err(e) => warnings.push(e.to_string()),
+            }
+        }
+
+        (blocks, warnings)
+    }
+
+    #[test]
+    fn parse_start_of_stream() {
+        let (blocks, warnings) = parse(include_str!("../testdata/progress/start.txt"));
+
+        assert!(warnings.is_empty());
+        assert_eq!(1, blocks.len());
+        assert_eq!(None, blocks[0].bitrate);
+        assert_eq!(Some(44), blocks[0].total_size);
+        assert_eq!(None, blocks[0].out_time_us);
+        assert_eq!(None, blocks[0].out_time);
+        assert_eq!(None, blocks[0].speed);
+        assert_eq!(Duration::ZERO, blocks[0].position());
+    }
+
+    #[test]
+    fn parse_encoding() {
+        let (blocks, warnings) = parse(include_str!("../testdata/progress/encoding.txt"));
+
+        assert!(warnings.is_empty());
+        assert_eq!(2, blocks.len());
+        assert_eq!(240, blocks[0].frame);
+        assert_eq!(Some(736_500), blocks[0].bitrate);
+        assert_eq!(Some(917_552), blocks[0].total_size);
+        assert_eq!(Some(Duration::microseconds(9_966_667)), blocks[0].out_time);
+        assert_eq!(Some(1.99), blocks[0].speed);
+        assert_eq!(Some(28.0), blocks[0].quantizer());
+        assert_eq!(2, blocks[1].drop_frames);
+        assert_eq!(None, blocks[1].quantizer());
+        assert_eq!(Some(Duration::microseconds(59_968_000)), blocks[1].out_time);
+    }
+
+    #[test]
+    fn parse_negative_times() {
+        let (blocks, warnings) = parse(include_str!("../testdata/progress/negative.txt"));
+
+        assert!(warnings.is_empty());
+        assert_eq!(2, blocks.len());
+        assert_eq!(Some(-2_077_252_342_775_808), blocks[0].out_time_us);
+        assert_eq!(
+            Some(-Duration::microseconds(2_077_252_342_775_808)),
+            blocks[0].out_time
+        );
+        assert_eq!(None, blocks[0].total_size);
+        assert_eq!(Some(Duration::milliseconds(-1500)), blocks[1].out_time);
+        assert_eq!(Duration::ZERO, blocks[1].position());
+    }
+
+    #[test]
+    fn parse_audio_copy() {
+        let (blocks, warnings) = parse(include_str!("../testdata/progress/copy_audio.txt"));
+
+        assert!(warnings.is_empty());
+        assert_eq!(1, blocks.len());
+        assert_eq!(0, blocks[0].frame);
+        assert_eq!(Some(128_000), blocks[0].bitrate);
+        assert_eq!(Some(613.0), blocks[0].speed);
+        assert_eq!(
+            Some(Duration::seconds(122) + Duration::milliseconds(880)),
+            blocks[0].out_time
+        );
+    }
+
+    #[test]
+    fn skip_malformed_values() {
+        let (blocks, warnings) = parse(include_str!("../testdata/progress/malformed.txt"));
+
+        assert_eq!(2, warnings.len());
+        assert!(warnings[0].starts_with("invalid progress value `bitrate=inf kbits/s`"));
+        assert!(warnings[1].starts_with("invalid progress value `speed=fast`"));
+        assert_eq!(1, blocks.len());
+        assert_eq!(24, blocks[0].frame);
+        assert_eq!(None, blocks[0].bitrate);
+        assert_eq!(Some(102_400), blocks[0].total_size);
+        assert_eq!(Some(Duration::milliseconds(1001)), blocks[0].out_time);
+        assert_eq!(None, blocks[0].speed);
+    }
 }
@@ -1,15 +1,17 @@
-use std::{collections::BTreeMap, path::Path, process::Command};
+use std::{collections::BTreeMap, path::Path};
 
 use anyhow::{ensure, Result};
 use serde::Deserialize;
 use time::Duration;
 
+use crate::tools;
+
 #[derive(Deserialize)]
 struct Report {
     format: Format,
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Default, Deserialize)]
 pub struct Format {
     pub filename: String,
     pub nb_streams: u32,
@@ -30,7 +32,7 @@ pub struct Format {
 }
 
 pub fn run(input: &Path) -> Result<Format> {
-    let output = Command::new("ffprobe")
+    let output = tools::ffprobe()
         .args([
             "-hide_banner",
             "-print_format",
@@ -78,9 +80,7 @@ mod de {
 
     impl<T> Default for FromStrVisitor<T> {
         fn default() -> Self {
-            Self {
-                ty: PhantomData::default(),
-            }
+            Self { ty: PhantomData }
         }
     }
 
@@ -0,0 +1,120 @@
+use std::{fs, io::ErrorKind, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use time::Duration;
+
+use crate::{ffmpeg::Progress, ffprobe::Format, stats::Event};
+
+/// Guard against encodings that end up larger than their input. The final output size is
+/// projected from the current size and position, once enough of the input has been processed.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct SizeGuard {
+    /// Maximum allowed size of the output, relative to the input size.
+    pub ratio: f64,
+    /// Fraction of the input that must be processed, before the projection is trusted.
+    pub after: f64,
+    /// Whether to stop the encoding, or only warn about it.
+    pub abort: bool,
+}
+
+impl SizeGuard {
+    /// Check the projected output size against the limit, returning an event if it's exceeded.
+    pub fn check(
+        &self,
+        timestamp: Duration,
+        progress: &Progress,
+        format: &Format,
+    ) -> Option<Event> {
+        let position = progress.out_time?.as_seconds_f64() / format.duration.as_seconds_f64();
+        if !position.is_finite() || position <= 0.0 || position < self.after {
+            return None;
+        }
+
+        let projected = (progress.total_size? as f64 / position.min(1.0)) as u64;
+        let limit = (format.size as f64 * self.ratio) as u64;
+
+        (projected > limit).then_some(Event::SizeGuard {
+            timestamp,
+            projected,
+            limit,
+            aborted: self.abort,
+        })
+    }
+}
+
+/// Delete the truncated output of an encoding that the size guard aborted, so it can't be mistaken
+/// for a complete file. It's fine if FFmpeg never got to create it.
+pub fn remove_partial(output: &Path) -> Result<()> {
+    match fs::remove_file(output) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e)
+            .with_context(|| format!("failed deleting the partial output `{}`", output.display())),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Duration;
+
+    use super::SizeGuard;
+    use crate::{ffmpeg::Progress, ffprobe::Format, stats::Event};
+
+    fn progress(seconds: i64, total_size: u64) -> Progress {
+        Progress {
+            out_time: Some(Duration::seconds(seconds)),
+            total_size: Some(total_size),
+            ..Progress::default()
+        }
+    }
+
+    #[test]
+    fn waits_for_enough_progress() {
+        let format = Format {
+            duration: Duration::seconds(100),
+            size: 1000,
+            ..Format::default()
+        };
+        let guard = SizeGuard {
+            ratio: 1.0,
+            after: 0.1,
+            abort: true,
+        };
+
+        assert!(guard
+            .check(Duration::ZERO, &progress(5, 500), &format)
+            .is_none());
+    }
+
+    #[test]
+    fn projects_final_size() {
+        let format = Format {
+            duration: Duration::seconds(100),
+            size: 1000,
+            ..Format::default()
+        };
+        let guard = SizeGuard {
+            ratio: 0.9,
+            after: 0.1,
+            abort: false,
+        };
+
+        assert!(guard
+            .check(Duration::ZERO, &progress(50, 440), &format)
+            .is_none());
+
+        match guard.check(Duration::ZERO, &progress(50, 460), &format) {
+            Some(Event::SizeGuard {
+                projected,
+                limit,
+                aborted,
+                ..
+            }) => {
+                assert_eq!(920, projected);
+                assert_eq!(900, limit);
+                assert!(!aborted);
+            }
+            _ => panic!("guard should trigger"),
+        }
+    }
+}
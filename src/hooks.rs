@@ -0,0 +1,553 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        mpsc::{self, Sender},
+        Mutex, OnceLock,
+    },
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use time::{Duration, Instant};
+
+use crate::notify::Finish;
+
+/// Maximum time to wait for a webhook server to accept and answer a request.
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Hooks from the configuration file, set once at startup.
+static HOOKS: OnceLock<Vec<Hook>> = OnceLock::new();
+
+/// Encodings that are currently running, tracked by their input file, so progress and the final
+/// result can be reported with the details given at the start.
+static RUNS: Mutex<Vec<Run>> = Mutex::new(Vec::new());
+
+/// Errors of failed hooks, collected while the terminal is in use and printed at the end.
+static FAILURES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Background thread that runs the hooks one after another, started with the first one. Hooks can
+/// take long, like a webhook waiting for its timeouts, so they never block the progress view.
+static WORKER: Mutex<Option<Worker>> = Mutex::new(None);
+
+/// Point in the life of an encoding, that hooks can be run on.
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Event {
+    Start,
+    /// Progress passed another step, like every 25%.
+    Milestone,
+    Finish,
+    Failure,
+    Cancel,
+}
+
+impl Event {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Start => "start",
+            Self::Milestone => "milestone",
+            Self::Finish => "finish",
+            Self::Failure => "failure",
+            Self::Cancel => "cancel",
+        }
+    }
+}
+
+/// Action to run on certain events, either a shell command or a webhook, configured in the
+/// `[[hooks]]` array of the configuration file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hook {
+    pub events: Vec<Event>,
+    /// Shell command, which gets the details in `FFPROG_*` environment variables.
+    pub command: Option<String>,
+    /// URL to post the details to, as JSON.
+    pub url: Option<String>,
+    /// Step in percent between milestones.
+    #[serde(default = "default_milestone")]
+    pub milestone: u8,
+}
+
+fn default_milestone() -> u8 {
+    25
+}
+
+struct Run {
+    input: PathBuf,
+    output: Option<PathBuf>,
+    duration: Duration,
+    start_time: Instant,
+    /// Progress in percent at the last update.
+    percent: u8,
+}
+
+struct Worker {
+    tx: Sender<(&'static Hook, Payload)>,
+    thread: JoinHandle<()>,
+}
+
+/// Details about an encoding, sent to the hooks.
+#[derive(Serialize)]
+struct Payload {
+    event: Event,
+    input: PathBuf,
+    output: Option<PathBuf>,
+    /// Reached milestone, in percent.
+    progress: Option<u8>,
+    /// Size of the output compared with the input.
+    size_ratio: Option<f64>,
+    /// Duration of the media, in seconds.
+    duration: f64,
+    /// Time spent encoding so far, in seconds.
+    elapsed: f64,
+    /// Location of the saved statistics.
+    stats: Option<PathBuf>,
+    error: Option<String>,
+}
+
+impl Payload {
+    fn env(&self) -> Vec<(&'static str, String)> {
+        let path = |path: &Path| path.to_string_lossy().into_owned();
+
+        [
+            ("FFPROG_EVENT", Some(self.event.as_str().to_owned())),
+            ("FFPROG_INPUT", Some(path(&self.input))),
+            ("FFPROG_OUTPUT", self.output.as_deref().map(path)),
+            ("FFPROG_PROGRESS", self.progress.map(|p| p.to_string())),
+            ("FFPROG_SIZE_RATIO", self.size_ratio.map(|r| r.to_string())),
+            ("FFPROG_DURATION", Some(self.duration.to_string())),
+            ("FFPROG_ELAPSED", Some(self.elapsed.to_string())),
+            ("FFPROG_STATS", self.stats.as_deref().map(path)),
+            ("FFPROG_ERROR", self.error.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect()
+    }
+}
+
+/// Check and set the hooks for all following encodings.
+pub fn init(hooks: Vec<Hook>) -> Result<()> {
+    for hook in &hooks {
+        match (&hook.command, &hook.url) {
+            (Some(_), None) => {}
+            (None, Some(url)) => ensure!(
+                url.starts_with("http://"),
+                "webhook URL `{url}` must start with `http://`"
+            ),
+            _ => bail!("hooks need either a `command` or a `url`"),
+        }
+        ensure!(
+            (1..=100).contains(&hook.milestone),
+            "hook milestones must be between 1 and 100 percent"
+        );
+    }
+
+    HOOKS.set(hooks).ok();
+    Ok(())
+}
+
+/// Report the start of an encoding.
+pub fn start(input: &Path, output: Option<&Path>, duration: Duration) {
+    let run = Run {
+        input: input.to_owned(),
+        output: output.map(ToOwned::to_owned),
+        duration,
+        start_time: Instant::now(),
+        percent: 0,
+    };
+
+    fire(&run, Event::Start, None, None, None, None);
+    RUNS.lock().expect("runs are never poisoned").push(run);
+}
+
+/// Report the overall progress of an encoding, from 0 to 1, which runs the milestone hooks each
+/// time the progress passes another step. Unknown inputs, like samples, are ignored.
+pub fn progress(input: &Path, ratio: f64) {
+    let payloads = {
+        let mut runs = RUNS.lock().expect("runs are never poisoned");
+        let Some(run) = runs.iter_mut().find(|run| run.input == input) else {
+            return;
+        };
+
+        let percent = (ratio.clamp(0.0, 1.0) * 100.0) as u8;
+        let previous = std::mem::replace(&mut run.percent, percent);
+
+        hooks(Event::Milestone)
+            .filter_map(|hook| {
+                let milestone = milestone(previous, percent, hook.milestone)?;
+                Some((
+                    hook,
+                    run.payload(Event::Milestone, Some(milestone), None, None, None),
+                ))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    for (hook, payload) in payloads {
+        send(hook, payload);
+    }
+}
+
+/// Report the end of an encoding. Unknown inputs, that never started, are ignored.
+pub fn finish(input: &Path, finish: &Finish, size_ratio: Option<f64>, stats: Option<&Path>) {
+    let run = {
+        let mut runs = RUNS.lock().expect("runs are never poisoned");
+        match runs.iter().position(|run| run.input == input) {
+            Some(index) => runs.remove(index),
+            None => return,
+        }
+    };
+
+    let (event, error) = match finish {
+        Finish::Completed => (Event::Finish, None),
+        Finish::Failed(e) => (Event::Failure, Some(e.as_str())),
+        Finish::Cancelled => (Event::Cancel, None),
+    };
+
+    fire(&run, event, None, size_ratio, stats, error);
+}
+
+/// Report the end of all encodings that are still running, like when a batch is cancelled.
+pub fn finish_all(finish: &Finish) {
+    let inputs = RUNS
+        .lock()
+        .expect("runs are never poisoned")
+        .iter()
+        .map(|run| run.input.clone())
+        .collect::<Vec<_>>();
+
+    for input in inputs {
+        self::finish(&input, finish, None, None);
+    }
+}
+
+/// Wait for all pending hooks to complete, then print the errors of all failed hooks so far.
+pub fn report() {
+    let worker = WORKER.lock().expect("worker is never poisoned").take();
+    if let Some(Worker { tx, thread }) = worker {
+        // Closing the channel lets the worker finish once it ran the remaining hooks.
+        drop(tx);
+        thread.join().ok();
+    }
+
+    for e in FAILURES
+        .lock()
+        .expect("failures are never poisoned")
+        .drain(..)
+    {
+        eprintln!("hook failed: {e}");
+    }
+}
+
+impl Run {
+    fn payload(
+        &self,
+        event: Event,
+        progress: Option<u8>,
+        size_ratio: Option<f64>,
+        stats: Option<&Path>,
+        error: Option<&str>,
+    ) -> Payload {
+        Payload {
+            event,
+            input: self.input.clone(),
+            output: self.output.clone(),
+            progress,
+            size_ratio,
+            duration: self.duration.as_seconds_f64(),
+            elapsed: self.start_time.elapsed().as_seconds_f64(),
+            stats: stats.map(ToOwned::to_owned),
+            error: error.map(ToOwned::to_owned),
+        }
+    }
+}
+
+fn fire(
+    run: &Run,
+    event: Event,
+    progress: Option<u8>,
+    size_ratio: Option<f64>,
+    stats: Option<&Path>,
+    error: Option<&str>,
+) {
+    for hook in hooks(event) {
+        send(hook, run.payload(event, progress, size_ratio, stats, error));
+    }
+}
+
+/// Queue the hook to be run on the worker thread, starting it if it isn't running yet.
+fn send(hook: &'static Hook, payload: Payload) {
+    let mut worker = WORKER.lock().expect("worker is never poisoned");
+    let worker = worker.get_or_insert_with(|| {
+        let (tx, rx) = mpsc::channel::<(&Hook, Payload)>();
+        let thread = thread::spawn(move || {
+            for (hook, payload) in rx {
+                record(run_hook(hook, &payload));
+            }
+        });
+
+        Worker { tx, thread }
+    });
+
+    worker.tx.send((hook, payload)).ok();
+}
+
+fn hooks(event: Event) -> impl Iterator<Item = &'static Hook> {
+    HOOKS
+        .get()
+        .into_iter()
+        .flatten()
+        .filter(move |hook| hook.events.contains(&event))
+}
+
+fn record(result: Result<()>) {
+    if let Err(e) = result {
+        FAILURES
+            .lock()
+            .expect("failures are never poisoned")
+            .push(format!("{e:#}"));
+    }
+}
+
+/// Highest milestone that was passed when the progress moved from `previous` to `current` percent,
+/// if any.
+fn milestone(previous: u8, current: u8, step: u8) -> Option<u8> {
+    let reached = current / step * step;
+    (reached > previous && reached > 0).then_some(reached)
+}
+
+fn run_hook(hook: &Hook, payload: &Payload) -> Result<()> {
+    if let Some(command) = &hook.command {
+        run_command(command, payload)
+            .with_context(|| format!("command `{command}` on {}", payload.event.as_str()))?;
+    }
+    if let Some(url) = &hook.url {
+        post(url, &serde_json::to_string(payload)?)
+            .with_context(|| format!("webhook `{url}` on {}", payload.event.as_str()))?;
+    }
+
+    Ok(())
+}
+
+/// Run the command in a shell, with the details in environment variables. Its output is captured,
+/// so it doesn't disturb the terminal.
+fn run_command(command: &str, payload: &Payload) -> Result<()> {
+    let output = Command::new("sh")
+        .args(["-c", command])
+        .envs(payload.env())
+        .stdin(Stdio::null())
+        .output()
+        .context("failed running the shell")?;
+
+    ensure!(
+        output.status.success(),
+        "{}: {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+
+    Ok(())
+}
+
+/// Post a JSON body to a plain HTTP URL, expecting a successful status code in return.
+fn post(url: &str, body: &str) -> Result<()> {
+    let rest = url
+        .strip_prefix("http://")
+        .context("only `http://` URLs are supported")?;
+    let (host, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    let addr = with_port(host)
+        .to_socket_addrs()
+        .with_context(|| format!("failed resolving `{host}`"))?
+        .next()
+        .with_context(|| format!("no address found for `{host}`"))?;
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    write!(
+        stream,
+        "POST {path} HTTP/1.1\r\n\
+         Host: {host}\r\n\
+         User-Agent: {}/{}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {body}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        body.len(),
+    )?;
+    stream.flush()?;
+
+    let mut status = String::new();
+    BufReader::new(stream).read_line(&mut status)?;
+
+    let code = status
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .with_context(|| format!("invalid response `{}`", status.trim()))?;
+    ensure!((200..300).contains(&code), "server answered with {code}");
+
+    Ok(())
+}
+
+/// Add the default HTTP port to the host, unless it has one. IPv6 addresses are enclosed in
+/// brackets, like `[::1]:8080`, so only a colon after them starts the port.
+fn with_port(host: &str) -> String {
+    let has_port = match host.rsplit_once(']') {
+        Some((_, rest)) => rest.starts_with(':'),
+        None => host.contains(':'),
+    };
+
+    if has_port {
+        host.to_owned()
+    } else {
+        format!("{host}:80")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        path::Path,
+        thread,
+    };
+
+    use serde_json::Value;
+    use time::{Duration, Instant};
+
+    use super::{Event, Hook, Run};
+
+    #[test]
+    fn passed_milestones() {
+        assert_eq!(None, super::milestone(0, 24, 25));
+        assert_eq!(Some(25), super::milestone(24, 25, 25));
+        assert_eq!(Some(50), super::milestone(10, 60, 25));
+        assert_eq!(None, super::milestone(50, 60, 25));
+        assert_eq!(Some(100), super::milestone(99, 100, 10));
+        assert_eq!(None, super::milestone(0, 0, 1));
+    }
+
+    #[test]
+    fn default_port() {
+        assert_eq!("example.com:80", super::with_port("example.com"));
+        assert_eq!("example.com:8080", super::with_port("example.com:8080"));
+        assert_eq!("[::1]:80", super::with_port("[::1]"));
+        assert_eq!("[::1]:8080", super::with_port("[::1]:8080"));
+    }
+
+    #[test]
+    fn command_environment() {
+        let dir = std::env::temp_dir().join(format!("ffprog-hook-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("env");
+
+        let run = Run {
+            input: "in.mkv".into(),
+            output: Some("out.mkv".into()),
+            duration: Duration::seconds(90),
+            start_time: Instant::now(),
+            percent: 0,
+        };
+        let payload = run.payload(Event::Finish, None, Some(0.5), None, None);
+        let mut hook = Hook {
+            events: vec![Event::Finish],
+            command: Some(format!(
+                "echo \"$FFPROG_EVENT $FFPROG_INPUT $FFPROG_OUTPUT $FFPROG_SIZE_RATIO \
+                 $FFPROG_DURATION ${{FFPROG_STATS-none}}\" > '{}'",
+                file.display()
+            )),
+            url: None,
+            milestone: 25,
+        };
+
+        super::run_hook(&hook, &payload).unwrap();
+        let output = fs::read_to_string(&file).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!("finish in.mkv out.mkv 0.5 90 none\n", output);
+
+        hook.command = Some("exit 3".to_owned());
+        assert!(super::run_hook(&hook, &payload).is_err());
+    }
+
+    #[test]
+    fn post_webhook() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks/ffprog", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            let mut length = 0;
+
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                request.push_str(&line);
+            }
+
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .into_inner()
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .unwrap();
+
+            (request, body)
+        });
+
+        let run = Run {
+            input: "in.mkv".into(),
+            output: Some("out.mkv".into()),
+            duration: Duration::seconds(90),
+            start_time: Instant::now(),
+            percent: 0,
+        };
+        let payload = run.payload(
+            Event::Failure,
+            None,
+            None,
+            Some(Path::new("in.mkv.stats")),
+            Some("exit code 1"),
+        );
+        let hook = Hook {
+            events: vec![Event::Failure],
+            command: None,
+            url: Some(url),
+            milestone: 25,
+        };
+        super::run_hook(&hook, &payload).unwrap();
+
+        let (request, body) = server.join().unwrap();
+        let body = serde_json::from_slice::<Value>(&body).unwrap();
+
+        assert!(request.starts_with("POST /hooks/ffprog HTTP/1.1\r\n"));
+        assert_eq!("failure", body["event"]);
+        assert_eq!("in.mkv", body["input"]);
+        assert_eq!("in.mkv.stats", body["stats"]);
+        assert_eq!("exit code 1", body["error"]);
+        assert_eq!(90.0, body["duration"]);
+        assert!(body["size_ratio"].is_null());
+    }
+}
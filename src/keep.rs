@@ -0,0 +1,203 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{ensure, Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{ffprobe, stats::Event};
+
+/// What to do with the input file, when the encoded output turns out smaller.
+#[derive(Clone, Copy, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum KeepMode {
+    /// Replace the input with the output, keeping the output's file extension.
+    Replace,
+    /// Move the input into the trash directory, and leave the output where it is.
+    Trash,
+}
+
+/// Post-processing step that compares the sizes of the input and output file, and only keeps the
+/// smaller one. A larger output is always deleted.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct KeepSmaller {
+    pub mode: KeepMode,
+    pub trash_dir: PathBuf,
+    /// Only decide which file to keep, without touching any of them.
+    pub dry_run: bool,
+}
+
+impl KeepSmaller {
+    /// Default location of the trash directory, inside the user's data directory.
+    pub fn default_trash_dir() -> Result<PathBuf> {
+        dirs::data_local_dir()
+            .map(|dir| dir.join(env!("CARGO_PKG_NAME")).join("trash"))
+            .context("failed finding the user's data directory")
+    }
+
+    /// Probe the output and keep whichever file is smaller, returning the decision together with
+    /// the path that now stands in for the input. It only differs from the input, if the output
+    /// replaced it with another file extension.
+    pub fn apply(&self, input: &Path, input_size: u64, output: &Path) -> Result<(Event, PathBuf)> {
+        let output_size = ffprobe::run(output)
+            .with_context(|| format!("failed probing output `{}`", output.display()))?
+            .size;
+
+        self.resolve(input, input_size, output, output_size)
+    }
+
+    fn resolve(
+        &self,
+        input: &Path,
+        input_size: u64,
+        output: &Path,
+        output_size: u64,
+    ) -> Result<(Event, PathBuf)> {
+        let kept_output = output_size < input_size;
+        let mut kept = input.to_owned();
+
+        if !self.dry_run {
+            if !kept_output {
+                fs::remove_file(output)?;
+            } else {
+                match self.mode {
+                    KeepMode::Replace => {
+                        let target = input.with_extension(output.extension().unwrap_or_default());
+                        ensure!(
+                            target == input || !target.exists(),
+                            "can't replace the input, as `{}` already exists",
+                            target.display()
+                        );
+                        move_file(output, &target)?;
+
+                        if target != input {
+                            fs::remove_file(input)?;
+                        }
+                        kept = target;
+                    }
+                    KeepMode::Trash => {
+                        let name = input.file_name().context("input has no file name")?;
+                        fs::create_dir_all(&self.trash_dir)?;
+                        move_file(input, &unused_path(&self.trash_dir, Path::new(name)))?;
+                    }
+                }
+            }
+        }
+
+        let event = Event::KeepSmaller {
+            input_size,
+            output_size,
+            kept_output,
+            dry_run: self.dry_run,
+        };
+
+        Ok((event, kept))
+    }
+}
+
+/// Find a path for the file name inside the directory, that isn't taken yet. Taken names get a
+/// counter appended to their stem, like `movie.1.mkv`.
+fn unused_path(dir: &Path, name: &Path) -> PathBuf {
+    let mut path = dir.join(name);
+    let mut counter = 1;
+
+    while path.exists() {
+        let mut file_name = name.file_stem().unwrap_or_default().to_os_string();
+        file_name.push(format!(".{counter}"));
+        if let Some(extension) = name.extension() {
+            file_name.push(".");
+            file_name.push(extension);
+        }
+
+        path = dir.join(file_name);
+        counter += 1;
+    }
+
+    path
+}
+
+/// Move a file, falling back to a copy if it has to cross file systems. The copy is written next
+/// to the target first, so the target is replaced atomically in either case.
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    let mut temp = to.as_os_str().to_os_string();
+    temp.push(".tmp");
+
+    fs::copy(from, &temp)
+        .with_context(|| format!("failed moving `{}` to `{}`", from.display(), to.display()))?;
+    fs::rename(&temp, to)?;
+    fs::remove_file(from)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{KeepMode, KeepSmaller};
+    use crate::stats::Event;
+
+    #[test]
+    fn keep_smaller_file() {
+        let dir = std::env::temp_dir().join(format!("ffprog-keep-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let input = dir.join("a.mkv");
+        let output = dir.join("out.mp4");
+        let keep = KeepSmaller {
+            mode: KeepMode::Replace,
+            trash_dir: dir.join("trash"),
+            dry_run: false,
+        };
+
+        fs::write(&input, "input").unwrap();
+        fs::write(&output, "out").unwrap();
+        fs::write(dir.join("a.mp4"), "unrelated").unwrap();
+        assert!(keep.resolve(&input, 5, &output, 3).is_err());
+        assert!(input.exists());
+
+        fs::remove_file(dir.join("a.mp4")).unwrap();
+        let (_, kept) = keep.resolve(&input, 5, &output, 3).unwrap();
+        assert!(!input.exists());
+        assert!(!output.exists());
+        assert_eq!(dir.join("a.mp4"), kept);
+        assert_eq!("out", fs::read_to_string(&kept).unwrap());
+
+        let input = dir.join("a.mp4");
+        fs::write(&output, "larger").unwrap();
+        let (event, _) = keep.resolve(&input, 3, &output, 6).unwrap();
+        assert!(matches!(
+            event,
+            Event::KeepSmaller {
+                kept_output: false,
+                ..
+            }
+        ));
+        assert!(input.exists());
+        assert!(!output.exists());
+
+        let keep = KeepSmaller {
+            mode: KeepMode::Trash,
+            ..keep
+        };
+        fs::create_dir_all(dir.join("trash")).unwrap();
+        fs::write(dir.join("trash/a.mp4"), "earlier").unwrap();
+        fs::write(&output, "o").unwrap();
+        keep.resolve(&input, 3, &output, 1).unwrap();
+        assert!(!input.exists());
+        assert!(output.exists());
+        assert_eq!(
+            "earlier",
+            fs::read_to_string(dir.join("trash/a.mp4")).unwrap()
+        );
+        assert!(dir.join("trash/a.1.mp4").exists());
+
+        fs::remove_dir_all(dir).ok();
+    }
+}
@@ -1,6 +1,13 @@
-use std::io::{self, Write};
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display},
+    fs,
+    io::{self, Write},
+    mem,
+    path::{Path, PathBuf},
+};
 
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, Context, Result};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
     execute,
@@ -10,48 +17,209 @@ use stats::Stats;
 use time::{Duration, Instant};
 use tui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Alignment, Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     symbols::Marker,
     text::{Span, Spans},
     widgets::{
         Axis, Block, BorderType, Borders, Chart, Clear, Dataset, Gauge, GraphType, Paragraph, Tabs,
     },
-    Terminal,
+    Frame, Terminal,
 };
 
 use crate::{
-    cli::{Cli, Command, RunArgs},
+    batch::{Entry, Job, Journal, Options, Outcome, Queue, Session, Status},
+    cli::{
+        BatchArgs, Cli, Command, CrfSearchArgs, LiveArgs, NotifyArgs, QueueCommand, RunArgs,
+        TrialArgs,
+    },
+    downsample::{Buckets, Series},
     ffmpeg::{Progress, ProgressIter},
     ffprobe::Format,
-    values::{ChartValues, SparklineValues},
+    guard::SizeGuard,
+    notify::{Finish, Notification},
+    quality::{Analysis, Quality},
+    runner::{Message, Runner},
+    search::{Search, Target},
+    trial::{Estimate, SampleLabel, TempDir},
+    twopass::TwoPass,
+    usage::Usage,
+    values::{ChartValues, Smoothing, SparklineValues},
 };
 
 mod array;
+mod batch;
 mod cli;
+mod config;
+mod downsample;
 mod ffmpeg;
 mod ffprobe;
+mod guard;
+mod hooks;
+mod keep;
+mod notify;
+mod priority;
+mod quality;
+mod runner;
+mod search;
 mod stats;
+mod tools;
+mod trial;
+mod twopass;
+mod usage;
 mod values;
+mod verify;
 
 fn main() -> Result<()> {
-    let args = Cli::parse();
+    let mut cli = Cli::parse();
+    priority::init(cli.priority)?;
+
+    if let Some(mut args) = cli.run {
+        let config = args.config.load()?;
+        args.apply_config(&config)?;
+        cli.live.apply_config(&config)?;
+        if !args.load_stats {
+            tools::init(cli.tools.resolve(&config))?;
+            hooks::init(config.hooks)?;
+        }
 
-    if let Some(args) = args.run {
+        let start = Instant::now();
         let mut terminal = create_terminal()?;
 
         // Don't exit with an error here, first restore the terminal to normal mode and
         // then fail with the error.
-        let result = run(&mut terminal, &args);
+        let result = run(&mut terminal, &args, &cli.live);
+        let output = ffmpeg::output_path(&args.args);
+        // A failed encoding never reaches the keep-smaller step, so its files are still in place.
+        let (kept, size_ratio) = match &result {
+            Ok(encoded) => (encoded.kept.clone(), encoded.size_ratio),
+            Err(_) => (
+                args.input.clone(),
+                size_ratio(&args.input, output.as_deref()),
+            ),
+        };
+        let result = result.map(drop);
 
         // Ignore any errors while restoring the terminal. If we fail, there is no way of getting
         // back to normal mode. Therefore, we skip this error and return the result from the
         // main execution instead.
         destroy_terminal(terminal).ok();
 
+        if !args.load_stats {
+            let finish = Finish::from_result(&result);
+            let stats = args.save_stats.then(|| stats::path(&kept));
+
+            hooks::finish(&args.input, &finish, size_ratio, stats.as_deref());
+            hooks::report();
+            notify(
+                &cli.notify,
+                Notification {
+                    name: file_name(&args.input),
+                    finish,
+                    size_ratio,
+                    elapsed: start.elapsed(),
+                },
+            );
+        }
+
         result
-    } else if let Some(cmd) = args.cmd {
+    } else if let Some(cmd) = cli.cmd {
         match cmd {
+            Command::Batch(mut args) => {
+                let config = args.config.load()?;
+                args.apply_config(&config)?;
+                cli.live.apply_config(&config)?;
+                tools::init(cli.tools.resolve(&config))?;
+                hooks::init(config.hooks)?;
+
+                let (jobs, journal, skipped) = prepare_batch(&args)?;
+                run_batch(
+                    jobs,
+                    journal,
+                    skipped,
+                    cli.live.smoothing,
+                    &cli.live,
+                    &cli.notify,
+                )
+            }
+            Command::Trial(mut args) => {
+                let config = args.config.load()?;
+                args.apply_config(&config)?;
+                cli.live.apply_config(&config)?;
+                tools::init(cli.tools.resolve(&config))?;
+                run_trial(&args, &cli.live)
+            }
+            Command::CrfSearch(mut args) => {
+                let config = args.config.load()?;
+                args.apply_config(&config)?;
+                cli.live.apply_config(&config)?;
+                tools::init(cli.tools.resolve(&config))?;
+                hooks::init(config.hooks)?;
+
+                let mut search = Search::new(args.target(), args.min_crf, args.max_crf);
+
+                let start = Instant::now();
+                let mut terminal = create_terminal()?;
+                let result = crf_search(&mut terminal, &args, &mut search, &cli.live);
+                destroy_terminal(terminal).ok();
+
+                let finish = Finish::from_result(&result);
+                let size_ratio = size_ratio(&args.input, Some(&args.output));
+                let stats = args.save_stats.then(|| stats::path(&args.input));
+
+                hooks::finish(&args.input, &finish, size_ratio, stats.as_deref());
+                hooks::report();
+                notify(
+                    &cli.notify,
+                    Notification {
+                        name: file_name(&args.input),
+                        finish,
+                        size_ratio,
+                        elapsed: start.elapsed(),
+                    },
+                );
+
+                for attempt in search.attempts() {
+                    println!("{}", stats::Event::from(attempt));
+                }
+                if let Some(crf) = search.result() {
+                    println!("chosen CRF: {crf}");
+                }
+
+                result
+            }
+            Command::Queue { cmd } => match cmd {
+                QueueCommand::Resume(args) => {
+                    let path = match &args.queue_file {
+                        Some(path) => path.clone(),
+                        None => Journal::default_path()?,
+                    };
+                    let mut journal = Journal::load(path)?;
+
+                    let config = config::load(journal.options().config.as_deref())?;
+                    cli.live.apply_config(&config)?;
+                    tools::init(cli.tools.resolve(&config))?;
+                    hooks::init(config.hooks)?;
+
+                    let (jobs, skipped) = prepare_resume(&mut journal, args.retry_failed)?;
+                    run_batch(
+                        jobs,
+                        journal,
+                        skipped,
+                        cli.live.smoothing,
+                        &cli.live,
+                        &cli.notify,
+                    )
+                }
+                QueueCommand::Show { queue_file } => {
+                    let path = match queue_file {
+                        Some(path) => path,
+                        None => Journal::default_path()?,
+                    };
+                    print_journal(&Journal::load(path)?);
+                    Ok(())
+                }
+            },
             Command::Completions { shell } => {
                 cli::completions(shell);
                 Ok(())
@@ -64,100 +232,1263 @@ fn main() -> Result<()> {
     }
 }
 
-fn run(terminal: &mut Terminal<impl Backend + Write>, args: &RunArgs) -> Result<()> {
+/// Outcome of a single encoding, for the hooks and notifications.
+struct Encoded {
+    /// Path that stands in for the input afterwards, which differs if the keep-smaller step
+    /// replaced it under a new extension.
+    kept: PathBuf,
+    /// Size of the output compared with the input, taken before the keep-smaller step moved any
+    /// of them.
+    size_ratio: Option<f64>,
+}
+
+/// Run a single encoding, or only show its saved statistics.
+fn run(
+    terminal: &mut Terminal<impl Backend + Write>,
+    args: &RunArgs,
+    live: &LiveArgs,
+) -> Result<Encoded> {
+    let mut encoded = Encoded {
+        kept: args.input.clone(),
+        size_ratio: None,
+    };
     let stats = if args.load_stats {
         stats::load(&args.input)?
     } else {
         let ffprobe = ffprobe::run(&args.input)?;
-        let ffmpeg = ffmpeg::spawn(&args.args, args.overwrite)?;
+        let keep = args.handling.keep.keep_smaller()?;
+        hooks::start(
+            &args.input,
+            ffmpeg::output_path(&args.args).as_deref(),
+            ffprobe.duration,
+        );
+
+        let mut stats = if args.two_pass {
+            let mut two_pass = TwoPass::new()?;
 
-        let result = show_progress(terminal, &ffprobe, ffmpeg);
+            // The first pass writes no output, so there's nothing to guard.
+            let ffmpeg = ffmpeg::spawn(&two_pass.args(&args.args)?, true, live.stats_period())?;
+            let first_pass = show_progress(
+                terminal,
+                &ffprobe,
+                ffmpeg,
+                live.smoothing,
+                live,
+                None,
+                Some(&two_pass),
+            )?;
 
-        let history = result?;
-        let stats = Stats {
-            import: ffprobe,
-            history,
+            two_pass.next();
+            let ffmpeg = ffmpeg::spawn(
+                &two_pass.args(&args.args)?,
+                args.overwrite.enabled(),
+                live.stats_period(),
+            )?;
+            let mut stats = show_progress(
+                terminal,
+                &ffprobe,
+                ffmpeg,
+                live.smoothing,
+                live,
+                args.handling.guard.size_guard(),
+                Some(&two_pass),
+            )?;
+
+            stats.first_pass = first_pass.history;
+            stats
+        } else {
+            let ffmpeg = ffmpeg::spawn(&args.args, args.overwrite.enabled(), live.stats_period())?;
+            show_progress(
+                terminal,
+                &ffprobe,
+                ffmpeg,
+                live.smoothing,
+                live,
+                args.handling.guard.size_guard(),
+                None,
+            )?
         };
 
+        if stats.events.iter().any(stats::Event::is_abort) {
+            if let Some(output) = ffmpeg::output_path(&args.args) {
+                guard::remove_partial(&output)?;
+            }
+        }
+
+        encoded.size_ratio = size_ratio(&args.input, ffmpeg::output_path(&args.args).as_deref());
+        let verify = args.handling.verify.verify();
+
+        if stats.failure().is_none()
+            && (args.handling.passes.check_integrity
+                || args.handling.passes.quality
+                || verify.is_some()
+                || keep.is_some())
+        {
+            let output = ffmpeg::output_path(&args.args)
+                .context("failed finding the output file in the FFmpeg arguments")?;
+
+            run_passes(
+                terminal,
+                &mut stats,
+                &output,
+                args.handling.passes.check_integrity,
+                args.handling.passes.quality,
+                live,
+                None,
+            )?;
+
+            if let (Some(verify), None) = (verify, stats.failure()) {
+                stats.events.push(verify.run(&stats.import, &output));
+            }
+
+            if let (Some(keep), None) = (keep, stats.failure()) {
+                let (event, path) = keep.apply(&args.input, stats.import.size, &output)?;
+                stats.events.push(event);
+                encoded.kept = path;
+            }
+        }
+
         if args.save_stats {
-            stats::save(&stats, &args.input)?;
+            stats::save(&stats, &encoded.kept)?;
         }
 
         stats
     };
 
+    let failure = stats
+        .failure()
+        .filter(|_| !args.load_stats)
+        .map(ToString::to_string);
+
     if args.load_stats || args.show_stats {
-        show_stats(terminal, stats)?;
+        show_stats(terminal, stats, live.smoothing)?;
+    }
+
+    if let Some(failure) = failure {
+        bail!("{failure}");
+    }
+
+    Ok(encoded)
+}
+
+/// Inputs of a batch run that couldn't be prepared, by their id in the journal.
+type Skipped = Vec<(usize, anyhow::Error)>;
+
+/// Collect and probe all the inputs of a new batch run, and replace the persisted journal with it.
+/// Inputs that can't be probed are recorded as failed, instead of stopping the whole batch.
+fn prepare_batch(args: &BatchArgs) -> Result<(Vec<Job>, Journal, Skipped)> {
+    fs::create_dir_all(&args.output_dir).with_context(|| {
+        format!(
+            "failed creating the output directory `{}`",
+            args.output_dir.display()
+        )
+    })?;
+
+    let mut jobs = Vec::new();
+    let mut entries = Vec::new();
+    let mut skipped = Vec::new();
+
+    for (id, input) in batch::collect_inputs(&args.inputs)?.into_iter().enumerate() {
+        let output = batch::output_path(&input, &args.output_dir, args.extension.as_deref())?;
+        let job_args = args.job_args(&input, &output)?;
+
+        match ffprobe::run(&input) {
+            Ok(format) => jobs.push(Job {
+                id,
+                input: input.clone(),
+                output: output.clone(),
+                format,
+                args: job_args.clone(),
+            }),
+            Err(e) => skipped.push((id, e)),
+        }
+
+        entries.push(Entry::pending(input, output, job_args));
+    }
+
+    let path = match &args.queue_file {
+        Some(path) => path.clone(),
+        None => Journal::default_path()?,
+    };
+    if !args.force && path.exists() {
+        ensure!(
+            !Journal::load(path.clone())?.is_unfinished(),
+            "the queue `{}` has unfinished jobs, continue them with `ffprog queue resume` or start \
+             over with `--force`",
+            path.display()
+        );
+    }
+    let journal = Journal::create(path, entries, args.options()?)?;
+
+    Ok((jobs, journal, skipped))
+}
+
+/// Probe the inputs of all jobs in the journal that still need to run. Inputs that can't be probed
+/// anymore are recorded as failed.
+fn prepare_resume(journal: &mut Journal, retry_failed: bool) -> Result<(Vec<Job>, Skipped)> {
+    let mut jobs = Vec::new();
+    let mut skipped = Vec::new();
+
+    for id in journal.resume(retry_failed)? {
+        let entry = &journal.entries()[id];
+
+        match ffprobe::run(&entry.input) {
+            Ok(format) => jobs.push(Job {
+                id,
+                input: entry.input.clone(),
+                output: entry.output.clone(),
+                format,
+                args: entry.args.clone(),
+            }),
+            Err(e) => skipped.push((id, e)),
+        }
+    }
+
+    Ok((jobs, skipped))
+}
+
+fn run_batch(
+    jobs: Vec<Job>,
+    journal: Journal,
+    skipped: Skipped,
+    smoothing: Smoothing,
+    live: &LiveArgs,
+    notify_args: &NotifyArgs,
+) -> Result<()> {
+    let mut session = Session::new(journal, &jobs);
+    let count = jobs.len() + skipped.len();
+
+    for (id, e) in &skipped {
+        session.skip(*id, e)?;
+    }
+
+    let start = Instant::now();
+    let mut terminal = create_terminal()?;
+    let result = batch(&mut terminal, jobs, &mut session, smoothing, live);
+    destroy_terminal(terminal).ok();
+
+    let queue = &session.queue;
+    print_summary(queue);
+
+    let result = result.and_then(|()| {
+        ensure!(
+            queue.failed() == 0,
+            "{} of {} jobs failed",
+            queue.failed(),
+            queue.outcomes().len()
+        );
+        Ok(())
+    });
+
+    let finish = Finish::from_result(&result);
+    // Jobs that are still running were interrupted.
+    hooks::finish_all(&finish);
+    hooks::report();
+
+    let (saved, total) = queue.savings();
+    notify(
+        notify_args,
+        Notification {
+            name: format!("Batch of {count} files"),
+            finish,
+            size_ratio: (total > 0).then(|| (total as i64 - saved) as f64 / total as f64),
+            elapsed: start.elapsed(),
+        },
+    );
+
+    result
+}
+
+fn batch(
+    terminal: &mut Terminal<impl Backend + Write>,
+    jobs: Vec<Job>,
+    session: &mut Session,
+    smoothing: Smoothing,
+    live: &LiveArgs,
+) -> Result<()> {
+    if session.options().jobs > 1 {
+        return show_dashboard(terminal, jobs, session, smoothing, live);
+    }
+
+    for (index, job) in jobs.into_iter().enumerate() {
+        session.start(&job)?;
+
+        let options = session.options();
+        let result = ffmpeg::spawn(&job.args, options.overwrite, live.stats_period())
+            .and_then(|ffmpeg| {
+                show_progress(
+                    terminal,
+                    &job.format,
+                    ffmpeg,
+                    smoothing,
+                    live,
+                    options.size_guard,
+                    Some(&session.queue),
+                )
+            })
+            .and_then(|mut stats| {
+                run_passes(
+                    terminal,
+                    &mut stats,
+                    &job.output,
+                    options.check_integrity,
+                    options.quality,
+                    live,
+                    Some(&session.queue),
+                )?;
+
+                Ok(stats)
+            });
+
+        match result {
+            Ok(stats) => {
+                session.finish(index, job, stats)?;
+            }
+            Err(e) if e.is::<Cancelled>() => return Err(e),
+            Err(e) => session.fail(index, job, &e)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// State of a job in the dashboard.
+enum JobStatus {
+    Pending,
+    Running,
+    Checking,
+    Analyzing,
+    Done,
+    Failed,
+}
+
+struct JobState {
+    job: Option<Job>,
+    name: String,
+    duration: Duration,
+    status: JobStatus,
+    history: Vec<(Duration, Progress)>,
+    usage: Vec<(Duration, Usage)>,
+    /// Latest progress of the current analysis pass, once the encoding is done.
+    pass: Progress,
+    guard: Option<SizeGuard>,
+    events: Vec<stats::Event>,
+    analysis: Option<Analysis>,
+    quality: Option<Quality>,
+    view: ProgressView,
+}
+
+fn show_dashboard(
+    terminal: &mut Terminal<impl Backend>,
+    jobs: Vec<Job>,
+    session: &mut Session,
+    mut smoothing: Smoothing,
+    live: &LiveArgs,
+) -> Result<()> {
+    let mut states = jobs
+        .into_iter()
+        .map(|job| JobState {
+            name: job
+                .input
+                .file_name()
+                .unwrap_or(job.input.as_os_str())
+                .to_string_lossy()
+                .into_owned(),
+            duration: job.format.duration,
+            status: JobStatus::Pending,
+            history: Vec::new(),
+            usage: Vec::new(),
+            pass: Progress::default(),
+            guard: session.options().size_guard,
+            events: Vec::new(),
+            analysis: None,
+            quality: None,
+            view: ProgressView::new(&job.format, live.samples(), smoothing),
+            job: Some(job),
+        })
+        .collect::<Vec<_>>();
+
+    let mut runner = Runner::new(session.options().jobs.into(), live.progress_source);
+    let mut next = 0;
+    let mut selection = 0;
+    let mut focus = false;
+
+    terminal.draw(|f| f.render_widget(Clear, f.size()))?;
+
+    loop {
+        while !runner.is_full() && next < states.len() {
+            let state = &mut states[next];
+            let job = state.job.as_ref().expect("pending jobs always exist");
+            session.start(job)?;
+
+            match ffmpeg::spawn(&job.args, session.options().overwrite, live.stats_period()) {
+                Ok(ffmpeg) => {
+                    runner.start(next, ffmpeg);
+                    state.status = JobStatus::Running;
+                }
+                Err(e) => {
+                    let job = state.job.take().expect("pending jobs always exist");
+                    session.fail(next, job, &e)?;
+                    state.status = JobStatus::Failed;
+                }
+            }
+
+            next += 1;
+        }
+
+        if runner.is_idle() && next >= states.len() {
+            return Ok(());
+        }
+
+        terminal.draw(|f| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(3), Constraint::Percentage(100)])
+                .split(f.size());
+
+            let in_progress = states
+                .iter()
+                .map(|s| match s.status {
+                    JobStatus::Running => s.view.position(),
+                    JobStatus::Checking | JobStatus::Analyzing => s.duration,
+                    _ => Duration::ZERO,
+                })
+                .sum();
+            let title = if focus {
+                format!("Focus: {} (Esc to go back)", states[selection].name)
+            } else {
+                "Jobs (Enter to focus)".to_owned()
+            };
+
+            f.render_widget(session.queue.create(in_progress, &title), chunks[0]);
+
+            if focus {
+                states[selection].view.draw(f, chunks[1]);
+            } else {
+                draw_jobs(f, chunks[1], &states, selection);
+            }
+        })?;
+
+        while event::poll(std::time::Duration::ZERO)? {
+            if let Event::Key(event) = event::read()? {
+                match event.code {
+                    KeyCode::Char('q') => bail!(Cancelled),
+                    KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                        bail!(Cancelled)
+                    }
+                    KeyCode::Esc | KeyCode::Backspace if focus => focus = false,
+                    KeyCode::Esc => bail!(Cancelled),
+                    KeyCode::Enter => focus = !focus,
+                    KeyCode::Up => selection = selection.saturating_sub(1),
+                    KeyCode::Down => selection = (selection + 1).min(states.len() - 1),
+                    KeyCode::Char('s') => {
+                        smoothing = smoothing.next();
+                        for state in &mut states {
+                            state.view.set_smoothing(smoothing);
+                        }
+                    }
+                    KeyCode::Char(key @ ('+' | '-')) => {
+                        match runner.adjust_nice(if key == '+' { 1 } else { -1 }) {
+                            Ok(priority) => {
+                                for state in &mut states {
+                                    state.view.priority = priority.to_string();
+                                }
+                            }
+                            Err(e) => {
+                                states[selection].view.warning =
+                                    Some(format!("failed changing niceness: {e}"));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut timeout = live.redraw_interval();
+
+        while let Some(msg) = runner.recv(timeout) {
+            timeout = std::time::Duration::ZERO;
+
+            match msg {
+                Message::Progress {
+                    job,
+                    timestamp,
+                    progress,
+                    usage,
+                    input_read,
+                    warnings,
+                    ignored,
+                } => {
+                    let progress = *progress;
+                    let state = &mut states[job];
+                    let Some(spec) = &state.job else {
+                        continue;
+                    };
+
+                    state
+                        .events
+                        .extend(stats::Event::warnings(timestamp, warnings));
+                    // A triggered size guard is more important, so its warning stays.
+                    if ignored > 0 && !state.events.iter().any(stats::Event::is_size_guard) {
+                        state.view.warning =
+                            Some(format!("ignored {ignored} invalid progress values"));
+                    }
+
+                    if let JobStatus::Checking | JobStatus::Analyzing = state.status {
+                        state.pass = progress;
+                        continue;
+                    }
+
+                    if let Some(event) = state
+                        .guard
+                        .and_then(|g| g.check(timestamp, &progress, &spec.format))
+                    {
+                        if event.is_abort() {
+                            runner.stop(job);
+                        }
+                        state.guard = None;
+                        state.view.warning = Some(event.to_string());
+                        state.events.push(event);
+                    }
+
+                    state.history.push((timestamp, progress.clone()));
+                    state.view.update(timestamp, progress);
+                    if let Some(read) = input_read {
+                        state.view.input_read = Some(read);
+                    }
+                    hooks::progress(&spec.input, state.view.ratio());
+                    if let Some(usage) = usage {
+                        state.usage.push((timestamp, usage));
+                        state.view.update_usage(timestamp, usage);
+                    }
+                }
+                Message::Finished {
+                    job,
+                    mut result,
+                    log,
+                } => {
+                    let state = &mut states[job];
+                    if state.job.is_none() {
+                        continue;
+                    }
+
+                    let aborted = state.events.iter().any(stats::Event::is_abort);
+
+                    if result.is_ok() {
+                        match state.status {
+                            JobStatus::Checking => {
+                                state.events.push(stats::Event::integrity(log));
+                            }
+                            JobStatus::Analyzing => {
+                                if let Some(analysis) = state.analysis.take() {
+                                    match analysis.finish() {
+                                        Ok(quality) => state.quality = Some(quality),
+                                        Err(e) => result = Err(e),
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    if result.is_ok() && !aborted {
+                        match start_pass(&mut runner, job, state, session.options(), live) {
+                            Ok(true) => continue,
+                            Ok(false) => {}
+                            Err(e) => result = Err(e),
+                        }
+                    }
+
+                    let spec = state.job.take().expect("finished jobs always exist");
+
+                    match result {
+                        Err(e) if !aborted => {
+                            session.fail(job, spec, &e)?;
+                            state.status = JobStatus::Failed;
+                        }
+                        _ => {
+                            let stats = Stats {
+                                import: spec.format.clone(),
+                                history: mem::take(&mut state.history),
+                                events: mem::take(&mut state.events),
+                                quality: state.quality.take(),
+                                first_pass: Vec::new(),
+                                usage: mem::take(&mut state.usage),
+                            };
+                            state.status = if session.finish(job, spec, stats)? {
+                                JobStatus::Done
+                            } else {
+                                JobStatus::Failed
+                            };
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Start the next analysis pass of a job, once the previous step is done. Returns whether a pass
+/// was started, or `false` if there is none left.
+fn start_pass(
+    runner: &mut Runner,
+    index: usize,
+    state: &mut JobState,
+    options: &Options,
+    live: &LiveArgs,
+) -> Result<bool> {
+    let job = state.job.as_ref().expect("running jobs always exist");
+
+    if state.events.iter().any(stats::Event::is_failure) {
+        return Ok(false);
+    }
+
+    if matches!(state.status, JobStatus::Running) && options.check_integrity {
+        runner.start(
+            index,
+            ffmpeg::spawn_check(&job.output, live.stats_period())?,
+        );
+        state.status = JobStatus::Checking;
+        return Ok(true);
+    }
+
+    if matches!(state.status, JobStatus::Running | JobStatus::Checking) && options.quality {
+        let (analysis, ffmpeg) = Analysis::spawn(&job.output, &job.input, live.stats_period())?;
+        runner.start(index, ffmpeg);
+        state.analysis = Some(analysis);
+        state.status = JobStatus::Analyzing;
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+/// Draw the list of all jobs, each as a single line gauge with its current state.
+fn draw_jobs(f: &mut Frame<'_, impl Backend>, area: Rect, states: &[JobState], selection: usize) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let rows = inner.height as usize;
+    let offset = (selection + 1).saturating_sub(rows);
+
+    for (i, state) in states.iter().enumerate().skip(offset).take(rows) {
+        let progress = &state.view.progress;
+        let progress = match state.status {
+            JobStatus::Checking | JobStatus::Analyzing => &state.pass,
+            _ => progress,
+        };
+        let position = match state.status {
+            JobStatus::Checking | JobStatus::Analyzing => progress.position(),
+            _ => state.view.position(),
+        };
+        let ratio = match state.status {
+            JobStatus::Done => 1.0,
+            _ => (position.as_seconds_f64() / state.duration.as_seconds_f64()).clamp(0.0, 1.0),
+        };
+        let label = match state.status {
+            JobStatus::Pending => format!("{} | pending", state.name),
+            JobStatus::Running => {
+                let eta = state
+                    .view
+                    .eta()
+                    .map_or_else(|| "--:--:--".to_owned(), format_duration);
+                format!(
+                    "{} | {:.1}% | {:.2}x | ETA {eta}",
+                    state.name,
+                    ratio * 100.0,
+                    progress.speed.unwrap_or_default()
+                )
+            }
+            JobStatus::Checking | JobStatus::Analyzing => format!(
+                "{} | {} | {:.1}% | {:.2}x",
+                state.name,
+                if let JobStatus::Checking = state.status {
+                    "checking"
+                } else {
+                    "analyzing"
+                },
+                ratio * 100.0,
+                progress.speed.unwrap_or_default()
+            ),
+            JobStatus::Done => format!("{} | done", state.name),
+            JobStatus::Failed => format!("{} | failed", state.name),
+        };
+        let color = match state.status {
+            JobStatus::Failed => Color::Red,
+            _ if i == selection => Color::Green,
+            _ => Color::White,
+        };
+
+        f.render_widget(
+            Gauge::default()
+                .gauge_style(Style::default().fg(color).bg(Color::Black))
+                .ratio(ratio)
+                .label(label),
+            Rect {
+                y: inner.y + (i - offset) as u16,
+                height: 1,
+                ..inner
+            },
+        );
+    }
+}
+
+/// Extract the samples from the input, encode them with each of the argument sets and print the
+/// projected results for the full file.
+fn run_trial(args: &TrialArgs, live: &LiveArgs) -> Result<()> {
+    let format = ffprobe::run(&args.input)?;
+    let extension = match &args.extension {
+        Some(extension) => extension.as_str(),
+        None => args
+            .input
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .context("input has no valid file extension")?,
+    };
+
+    let dir = TempDir::new("trial")?;
+    let samples = trial::extract_samples(
+        &args.input,
+        &format,
+        args.samples.into(),
+        Duration::seconds_f64(args.sample_length),
+        dir.path(),
+        extension,
+    )?;
+
+    let encoder = SampleEncoder {
+        samples: &samples,
+        dir: dir.path(),
+        extension,
+        smoothing: live.smoothing,
+        live,
+    };
+
+    let mut terminal = create_terminal()?;
+    let result = trial(&mut terminal, args, &encoder);
+    destroy_terminal(terminal).ok();
+
+    print_trial(&format, args.sets().zip(result?));
+
+    Ok(())
+}
+
+fn trial(
+    terminal: &mut Terminal<impl Backend + Write>,
+    args: &TrialArgs,
+    encoder: &SampleEncoder<'_>,
+) -> Result<Vec<Estimate>> {
+    let samples = encoder.samples;
+    let mut queue = Queue::new(
+        args.sets()
+            .flat_map(|_| samples.iter().map(|sample| sample.duration))
+            .collect(),
+    );
+    let mut estimates = Vec::new();
+
+    for (set, name) in args.sets().enumerate() {
+        let estimate = encoder.encode(
+            terminal,
+            name,
+            &mut queue,
+            |input, output| args.sample_args(set, input, output),
+            |_, queue, encoded| {
+                queue.finish(
+                    set * samples.len() + encoded.index,
+                    SampleLabel {
+                        set: name.to_owned(),
+                        sample: encoded.index + 1,
+                    },
+                    Outcome::Done {
+                        input_size: encoded.sample.size,
+                        output_size: encoded.output_size,
+                    },
+                );
+                Ok(())
+            },
+        )?;
+
+        estimates.push(estimate);
+    }
+
+    Ok(estimates)
+}
+
+/// Sample that was just encoded by a [`SampleEncoder`].
+struct EncodedSample<'a> {
+    index: usize,
+    /// Probed sample, named after the round and position it was encoded in.
+    sample: &'a Format,
+    input: &'a Path,
+    output: &'a Path,
+    output_size: u64,
+}
+
+/// Samples of an input, that are encoded in several rounds with different arguments.
+struct SampleEncoder<'a> {
+    samples: &'a [Format],
+    dir: &'a Path,
+    extension: &'a str,
+    smoothing: Smoothing,
+    live: &'a LiveArgs,
+}
+
+impl SampleEncoder<'_> {
+    /// Encode every sample with the arguments from `sample_args`, showing the progress of each one
+    /// under the given label, and total up the results. After each sample, `done` gets the encoded
+    /// sample, like to analyze it or update the header.
+    fn encode<B: Backend, H: Header>(
+        &self,
+        terminal: &mut Terminal<B>,
+        label: &str,
+        header: &mut H,
+        sample_args: impl Fn(&Path, &Path) -> Result<Vec<String>>,
+        mut done: impl FnMut(&mut Terminal<B>, &mut H, EncodedSample<'_>) -> Result<()>,
+    ) -> Result<Estimate> {
+        let mut estimate = Estimate::default();
+
+        for (i, sample) in self.samples.iter().enumerate() {
+            let input = Path::new(&sample.filename);
+            let output = self.dir.join(format!("output-{i}.{}", self.extension));
+            let ffmpeg = ffmpeg::spawn(
+                &sample_args(input, &output)?,
+                true,
+                self.live.stats_period(),
+            )?;
+
+            // Show which arguments and sample are running, instead of the temporary file.
+            let current = Format {
+                filename: format!("{label}, sample {} of {}", i + 1, self.samples.len()),
+                ..sample.clone()
+            };
+
+            let start_time = Instant::now();
+            show_progress(
+                terminal,
+                &current,
+                ffmpeg,
+                self.smoothing,
+                self.live,
+                None,
+                Some(&*header),
+            )?;
+            let encode_time = start_time.elapsed();
+
+            let output_size = fs::metadata(&output)
+                .with_context(|| format!("{label} produced no output"))?
+                .len();
+            estimate.add(sample.duration, output_size, encode_time);
+
+            let encoded = EncodedSample {
+                index: i,
+                sample: &current,
+                input,
+                output: &output,
+                output_size,
+            };
+            done(terminal, header, encoded)?;
+        }
+
+        Ok(estimate)
+    }
+}
+
+/// Bisect the CRF range by encoding samples of the input, then encode the full file with the chosen
+/// value. All attempts are recorded in the statistics of the final encoding.
+fn crf_search(
+    terminal: &mut Terminal<impl Backend + Write>,
+    args: &CrfSearchArgs,
+    search: &mut Search,
+    live: &LiveArgs,
+) -> Result<()> {
+    let target = args.target();
+    ensure!(
+        !matches!(target, Target::Vmaf(_)) || ffmpeg::has_filter("libvmaf"),
+        "a VMAF target requires FFmpeg to be built with `libvmaf`"
+    );
+
+    let format = ffprobe::run(&args.input)?;
+    hooks::start(&args.input, Some(&args.output), format.duration);
+
+    let extension = args
+        .output
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .context("output has no valid file extension")?;
+
+    let dir = TempDir::new("crf-search")?;
+    let samples = trial::extract_samples(
+        &args.input,
+        &format,
+        args.samples.into(),
+        Duration::seconds_f64(args.sample_length),
+        dir.path(),
+        extension,
+    )?;
+    let encoder = SampleEncoder {
+        samples: &samples,
+        dir: dir.path(),
+        extension,
+        smoothing: live.smoothing,
+        live,
+    };
+
+    while let Some(crf) = search.next() {
+        let mut vmaf = Vec::new();
+        let estimate = encoder.encode(
+            terminal,
+            &format!("CRF {crf}"),
+            search,
+            |input, output| args.encode_args(crf, input, output),
+            |terminal, search, encoded| {
+                if let Target::Vmaf(_) = target {
+                    let (analysis, ffmpeg) =
+                        Analysis::spawn(encoded.output, encoded.input, live.stats_period())?;
+                    show_pass(
+                        terminal,
+                        "Quality analysis",
+                        encoded.sample,
+                        ffmpeg,
+                        live,
+                        Some(&*search),
+                    )?;
+                    vmaf.extend(analysis.finish()?.vmaf);
+                }
+                Ok(())
+            },
+        )?;
+
+        let vmaf = (!vmaf.is_empty()).then(|| vmaf.iter().sum::<f64>() / vmaf.len() as f64);
+        search.record(crf, vmaf, estimate.projected_size(format.duration));
+    }
+
+    let crf = search.result().with_context(|| {
+        format!(
+            "none of the CRF values between {} and {} meets the target",
+            args.min_crf, args.max_crf
+        )
+    })?;
+
+    let ffmpeg = ffmpeg::spawn(
+        &args.encode_args(crf, &args.input, &args.output)?,
+        args.overwrite.enabled(),
+        live.stats_period(),
+    )?;
+    let mut stats = show_progress(
+        terminal,
+        &format,
+        ffmpeg,
+        live.smoothing,
+        live,
+        None,
+        Some(&*search),
+    )?;
+    stats
+        .events
+        .splice(0..0, search.attempts().iter().map(Into::into));
+
+    if args.save_stats {
+        stats::save(&stats, &args.input)?;
+    }
+
+    let failure = stats.failure().map(ToString::to_string);
+
+    if args.show_stats {
+        show_stats(terminal, stats, live.smoothing)?;
+    }
+
+    if let Some(failure) = failure {
+        bail!("{failure}");
+    }
+
+    Ok(())
+}
+
+fn print_trial<'a>(format: &Format, estimates: impl Iterator<Item = (&'a str, Estimate)>) {
+    println!(
+        "{:<20}{:>24}{:>18}{:>10}",
+        "arguments", "projected size", "bitrate", "speed"
+    );
+
+    for (name, estimate) in estimates {
+        let size = estimate.projected_size(format.duration);
+        let ratio = if format.size > 0 {
+            size as f64 / format.size as f64 * 100.0
+        } else {
+            0.0
+        };
+
+        println!(
+            "{name:<20}{:>24}{:>18}{:>10}",
+            format!("{} ({ratio:.1}%)", format_size(size)),
+            format!("{:.1} kbits/s", estimate.bitrate() as f64 / 1000.0),
+            format!("{:.2}x", estimate.speed())
+        );
+    }
+}
+
+fn print_journal(journal: &Journal) {
+    for entry in journal.entries() {
+        let status = match &entry.status {
+            Status::Pending => "pending",
+            Status::Running => "running",
+            Status::Done => "done",
+            Status::Failed(_) => "failed",
+        };
+
+        println!(
+            "{status:<8}{} -> {}",
+            entry.input.display(),
+            entry.output.display()
+        );
+
+        if let Status::Failed(e) = &entry.status {
+            println!("        {}", e.trim());
+        }
+        if let Some(stats) = &entry.stats {
+            println!("        stats: {}", stats.display());
+        }
+    }
+}
+
+fn print_summary(queue: &Queue) {
+    for (input, outcome) in queue.outcomes() {
+        match outcome {
+            Outcome::Done {
+                input_size,
+                output_size,
+            } => println!(
+                "done    {} ({} -> {})",
+                input.display(),
+                format_size(*input_size),
+                format_size(*output_size)
+            ),
+            Outcome::Failed(e) => println!("failed  {}: {}", input.display(), e.trim()),
+        }
+    }
+}
+
+/// Send the notification to all channels the user asked for. Failures are only printed, as the
+/// encoding itself is done at this point.
+fn notify(args: &NotifyArgs, notification: Notification) {
+    for e in notify::send(&notify::notifiers(&args.notify), &notification) {
+        eprintln!("failed sending notification: {e:#}");
+    }
+}
+
+/// Size of the output compared with the input, if both exist.
+fn size_ratio(input: &Path, output: Option<&Path>) -> Option<f64> {
+    let input = fs::metadata(input).ok()?.len();
+    let output = fs::metadata(output?).ok()?.len();
+    (input > 0).then(|| output as f64 / input as f64)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn create_terminal() -> Result<Terminal<impl Backend + Write>> {
+    terminal::enable_raw_mode()?;
+
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(stdout);
+
+    Terminal::new(backend).map_err(Into::into)
+}
+
+fn destroy_terminal(mut terminal: Terminal<impl Backend + Write>) -> Result<()> {
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal::disable_raw_mode()?;
+    terminal.show_cursor()?;
+
+    Ok(())
+}
+
+fn show_progress(
+    terminal: &mut Terminal<impl Backend>,
+    ffprobe: &Format,
+    mut ffmpeg: ProgressIter,
+    mut smoothing: Smoothing,
+    live: &LiveArgs,
+    mut guard: Option<SizeGuard>,
+    header: Option<&dyn Header>,
+) -> Result<Stats> {
+    let mut history = Vec::new();
+    let mut usage_history = Vec::new();
+    let mut events = Vec::new();
+    let mut view = ProgressView::new(ffprobe, live.samples(), smoothing);
+    view.phase = header.and_then(Header::phase);
+    let start_time = Instant::now();
+
+    terminal.draw(|f| f.render_widget(Clear, f.size()))?;
+
+    loop {
+        terminal.draw(|f| {
+            let area = draw_header(
+                f,
+                header,
+                view.position(),
+                &format!("Current: {}", ffprobe.filename),
+            );
+
+            view.draw(f, area);
+        })?;
+
+        while event::poll(live.redraw_interval())? {
+            if let Event::Key(event) = event::read()? {
+                match event.code {
+                    KeyCode::Char('q') | KeyCode::Esc => bail!(Cancelled),
+                    KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                        bail!(Cancelled)
+                    }
+                    KeyCode::Char('s') => {
+                        smoothing = smoothing.next();
+                        view.set_smoothing(smoothing);
+                    }
+                    KeyCode::Char(key @ ('+' | '-')) => {
+                        match ffmpeg.adjust_nice(if key == '+' { 1 } else { -1 }) {
+                            Ok(priority) => view.priority = priority.to_string(),
+                            Err(e) => view.warning = Some(format!("failed changing niceness: {e}")),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        match ffmpeg.next() {
+            Some(res) => {
+                let progress = res?;
+                let timestamp = start_time.elapsed();
+
+                if let Some(event) = guard.and_then(|g| g.check(timestamp, &progress, ffprobe)) {
+                    guard = None;
+                    view.warning = Some(event.to_string());
+                    events.push(event);
+                }
+
+                events.extend(stats::Event::warnings(timestamp, ffmpeg.take_warnings()));
+                // A triggered size guard is more important, so its warning stays.
+                if ffmpeg.ignored() > 0 && !events.iter().any(stats::Event::is_size_guard) {
+                    view.warning = Some(format!(
+                        "ignored {} invalid progress values",
+                        ffmpeg.ignored()
+                    ));
+                }
+
+                history.push((timestamp, progress.clone()));
+                view.update(timestamp, progress);
+                if let Some(read) = ffmpeg.input_read(live.progress_source) {
+                    view.input_read = Some(read);
+                }
+                hooks::progress(Path::new(&ffprobe.filename), view.ratio());
+                if let Some(usage) = ffmpeg.usage() {
+                    usage_history.push((timestamp, usage));
+                    view.update_usage(timestamp, usage);
+                }
+
+                // Leaving early drops the iterator, which kills the FFmpeg process.
+                if events.iter().any(stats::Event::is_abort) {
+                    break;
+                }
+            }
+            None => break,
+        }
     }
 
-    Ok(())
+    Ok(Stats {
+        import: ffprobe.clone(),
+        history,
+        events,
+        quality: None,
+        first_pass: Vec::new(),
+        usage: usage_history,
+    })
 }
 
-fn create_terminal() -> Result<Terminal<impl Backend + Write>> {
-    terminal::enable_raw_mode()?;
-
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-
-    Terminal::new(backend).map_err(Into::into)
-}
+/// Run the enabled analysis passes over a finished encoding, adding their results to the
+/// statistics. Passes are skipped once the encoding counts as failed.
+fn run_passes(
+    terminal: &mut Terminal<impl Backend>,
+    stats: &mut Stats,
+    output: &Path,
+    check_integrity: bool,
+    quality: bool,
+    live: &LiveArgs,
+    header: Option<&dyn Header>,
+) -> Result<()> {
+    if check_integrity && stats.failure().is_none() {
+        let ffmpeg = ffmpeg::spawn_check(output, live.stats_period())?;
+        let log = show_pass(
+            terminal,
+            "Integrity check",
+            &stats.import,
+            ffmpeg,
+            live,
+            header,
+        )?;
+        stats.events.push(stats::Event::integrity(log));
+    }
 
-fn destroy_terminal(mut terminal: Terminal<impl Backend + Write>) -> Result<()> {
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal::disable_raw_mode()?;
-    terminal.show_cursor()?;
+    if quality && stats.failure().is_none() {
+        let input = Path::new(&stats.import.filename);
+        let (analysis, ffmpeg) = Analysis::spawn(output, input, live.stats_period())?;
+        show_pass(
+            terminal,
+            "Quality analysis",
+            &stats.import,
+            ffmpeg,
+            live,
+            header,
+        )?;
+        stats.quality = Some(analysis.finish()?);
+    }
 
     Ok(())
 }
 
-fn show_progress(
+/// Show the progress of an analysis pass over an encoded file, like the integrity check, returning
+/// all errors that FFmpeg logged.
+fn show_pass(
     terminal: &mut Terminal<impl Backend>,
+    name: &str,
     ffprobe: &Format,
     mut ffmpeg: ProgressIter,
-) -> Result<Vec<(Duration, Progress)>> {
+    live: &LiveArgs,
+    header: Option<&dyn Header>,
+) -> Result<Vec<String>> {
     let mut progress = Progress::default();
-    let mut history = Vec::new();
-    let mut fps = SparklineValues::new(|v| format!("FPS: {v:.1}"));
-    let mut speed = SparklineValues::new(|v| format!("Speed: {v:.2}x"));
-    let mut bitrate = ChartValues::new(ffprobe.bit_rate as f64, |v| {
-        format!("Bitrate: {:.1} kbits/s", v / 1000.0)
-    });
+    let mut warnings = Vec::<String>::new();
     let start_time = Instant::now();
-    let mut timestamp = Duration::ZERO;
 
     terminal.draw(|f| f.render_widget(Clear, f.size()))?;
 
     loop {
+        let log = ffmpeg.log();
+
         terminal.draw(|f| {
+            let area = draw_header(
+                f,
+                header,
+                ffprobe.duration,
+                &format!("{name}: {}", ffprobe.filename),
+            );
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([Constraint::Length(5), Constraint::Percentage(100)])
-                .split(f.size());
-
-            let lr = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-                .split(chunks[1]);
-
-            let left = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Length(3),
-                    Constraint::Length(6),
-                    Constraint::Length(6),
-                    Constraint::Percentage(100),
-                ])
-                .split(lr[0]);
-
-            let left_r1 = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Ratio(1, 3); 3])
-                .split(left[0]);
+                .constraints([Constraint::Length(3), Constraint::Min(0)])
+                .split(area);
 
             f.render_widget(
                 Gauge::default()
@@ -165,9 +1496,9 @@ fn show_progress(
                         Block::default()
                             .title(Span::styled(
                                 format!(
-                                    "Progress / Run-time: {} / Out-time: {}",
-                                    format_duration(timestamp),
-                                    format_duration(progress.out_time)
+                                    "{name} / Run-time: {} / Speed: {:.2}x",
+                                    format_duration(start_time.elapsed()),
+                                    progress.speed.unwrap_or_default()
                                 ),
                                 Style::default().fg(Color::Blue),
                             ))
@@ -177,73 +1508,45 @@ fn show_progress(
                     )
                     .gauge_style(Style::default().fg(Color::White).bg(Color::Black))
                     .ratio(
-                        (progress.out_time.as_seconds_f64() / ffprobe.duration.as_seconds_f64())
-                            .max(0.0)
-                            .min(1.0),
+                        (progress.position().as_seconds_f64() / ffprobe.duration.as_seconds_f64())
+                            .clamp(0.0, 1.0),
                     ),
                 chunks[0],
             );
 
+            // Ignored progress values are listed after the errors, so they don't get lost in them.
+            let rows = chunks[1].height.saturating_sub(2) as usize;
+            let lines = log
+                .iter()
+                .map(|line| (line.trim(), Color::Red))
+                .chain(warnings.iter().map(|line| (line.as_str(), Color::Yellow)))
+                .collect::<Vec<_>>();
+            let lines = lines
+                .iter()
+                .skip(lines.len().saturating_sub(rows))
+                .map(|&(line, color)| Spans::from(Span::styled(line, Style::default().fg(color))))
+                .collect::<Vec<_>>();
+
             f.render_widget(
-                Paragraph::new(progress.frame.to_string()).block(
-                    Block::default()
-                        .title(Span::styled("Frame", Style::default().fg(Color::Blue)))
-                        .borders(Borders::ALL)
-                        .border_type(BorderType::Rounded),
-                ),
-                left_r1[0],
-            );
-            f.render_widget(
-                Paragraph::new({
-                    if progress.total_size > 1_000_000_000 {
-                        format!("{:.2} GiB", progress.total_size as f64 / 1_000_000_000.0)
-                    } else if progress.total_size > 1_000_000 {
-                        format!("{:.2} MiB", progress.total_size as f64 / 1_000_000.0)
-                    } else if progress.total_size > 1_000 {
-                        format!("{:.2} KiB", progress.total_size as f64 / 1_000.0)
-                    } else {
-                        format!("{} B", progress.total_size)
-                    }
-                })
-                .block(
-                    Block::default()
-                        .title(Span::styled("Total size", Style::default().fg(Color::Blue)))
-                        .borders(Borders::ALL)
-                        .border_type(BorderType::Rounded),
-                ),
-                left_r1[1],
-            );
-            f.render_widget(
-                Paragraph::new(format!(
-                    "{} / {}",
-                    progress.dup_frames, progress.drop_frames
-                ))
-                .block(
+                Paragraph::new(lines).block(
                     Block::default()
                         .title(Span::styled(
-                            "Dup / Drop frames",
+                            format!("Errors: {} / Warnings: {}", log.len(), ffmpeg.ignored()),
                             Style::default().fg(Color::Blue),
                         ))
                         .borders(Borders::ALL)
                         .border_type(BorderType::Rounded),
                 ),
-                left_r1[2],
+                chunks[1],
             );
-
-            f.render_widget(fps.create(left[1]), left[1]);
-            f.render_widget(speed.create(left[2]), left[2]);
-
-            f.render_widget(bitrate.create(), lr[1]);
         })?;
 
-        while event::poll(std::time::Duration::from_millis(250))? {
+        while event::poll(live.redraw_interval())? {
             if let Event::Key(event) = event::read()? {
                 match event.code {
-                    KeyCode::Char('q') | KeyCode::Esc => {
-                        bail!("encoding cancelled by user");
-                    }
+                    KeyCode::Char('q') | KeyCode::Esc => bail!(Cancelled),
                     KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::CONTROL) => {
-                        bail!("encoding cancelled by user");
+                        bail!(Cancelled)
                     }
                     _ => {}
                 }
@@ -253,46 +1556,485 @@ fn show_progress(
         match ffmpeg.next() {
             Some(res) => {
                 progress = res?;
-                timestamp = start_time.elapsed();
-                history.push((timestamp, progress.clone()));
+                warnings.extend(ffmpeg.take_warnings());
+            }
+            None => return Ok(ffmpeg.log()),
+        }
+    }
+}
+
+/// Draw the header panel on top, if there is one, and return the remaining area.
+fn draw_header(
+    f: &mut Frame<'_, impl Backend>,
+    header: Option<&dyn Header>,
+    in_progress: Duration,
+    title: &str,
+) -> Rect {
+    match header {
+        Some(header) => {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(3), Constraint::Percentage(100)])
+                .split(f.size());
+
+            f.render_widget(header.create(in_progress, title), chunks[0]);
+            chunks[1]
+        }
+        None => f.size(),
+    }
+}
+
+/// Panel shown above the progress view, giving context about the overall session, like the queue
+/// of a batch run.
+trait Header {
+    fn create(&self, in_progress: Duration, title: &str) -> Paragraph<'_>;
+
+    /// Current phase, if the encoding is made up of several runs of FFmpeg.
+    fn phase(&self) -> Option<Phase> {
+        None
+    }
+}
+
+/// Position of a single FFmpeg run within a multi-phase encoding, like the passes of a two-pass
+/// encoding.
+#[derive(Clone, Copy)]
+struct Phase {
+    index: usize,
+    count: usize,
+    name: &'static str,
+}
+
+type Labeler = fn(f64) -> String;
+
+/// Live view of a single running encoding, showing the latest progress and the recent history of
+/// the most important values.
+struct ProgressView {
+    progress: Progress,
+    timestamp: Duration,
+    duration: Duration,
+    /// Size of the input file.
+    size: u64,
+    /// Bytes of the input that FFmpeg read so far, if the progress is tracked by the input.
+    input_read: Option<u64>,
+    /// Warning to show next to the progress, like a triggered size guard.
+    warning: Option<String>,
+    /// Phase of a multi-phase encoding, making the gauge show the combined progress.
+    phase: Option<Phase>,
+    /// Scheduling settings of FFmpeg, like its nice value.
+    priority: String,
+    fps: SparklineValues<Labeler>,
+    speed: SparklineValues<Labeler>,
+    quantizer: SparklineValues<Labeler>,
+    bitrate: ChartValues<Labeler>,
+    /// Latest resource usage sample of FFmpeg, to calculate the rates from.
+    usage: Option<(Duration, Usage)>,
+    cpu: SparklineValues<Labeler>,
+    memory: SparklineValues<Labeler>,
+    read: SparklineValues<Labeler>,
+    write: SparklineValues<Labeler>,
+}
+
+impl ProgressView {
+    fn new(ffprobe: &Format, samples: usize, smoothing: Smoothing) -> Self {
+        let mut view = Self {
+            progress: Progress::default(),
+            timestamp: Duration::ZERO,
+            duration: ffprobe.duration,
+            size: ffprobe.size,
+            input_read: None,
+            warning: None,
+            phase: None,
+            priority: priority::current().to_string(),
+            fps: SparklineValues::new(samples, |v| format!("FPS: {v:.1}")),
+            speed: SparklineValues::new(samples, |v| format!("Speed: {v:.2}x")),
+            quantizer: SparklineValues::new(samples, |v| format!("Quantizer: {v:.1}")),
+            bitrate: ChartValues::new(samples, ffprobe.bit_rate as f64, |v| {
+                format!("Bitrate: {:.1} kbits/s", v / 1000.0)
+            }),
+            usage: None,
+            cpu: SparklineValues::new(samples, |v| format!("CPU: {v:.0}%")),
+            memory: SparklineValues::new(samples, |v| format!("Memory: {}", format_size(v as u64))),
+            read: SparklineValues::new(samples, |v| format!("Read: {}/s", format_size(v as u64))),
+            write: SparklineValues::new(samples, |v| format!("Write: {}/s", format_size(v as u64))),
+        };
+
+        view.set_smoothing(smoothing);
+        view
+    }
+
+    fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.fps.set_smoothing(smoothing);
+        self.speed.set_smoothing(smoothing);
+        self.quantizer.set_smoothing(smoothing);
+        self.bitrate.set_smoothing(smoothing);
+        self.cpu.set_smoothing(smoothing);
+        self.memory.set_smoothing(smoothing);
+        self.read.set_smoothing(smoothing);
+        self.write.set_smoothing(smoothing);
+    }
+
+    fn update(&mut self, timestamp: Duration, progress: Progress) {
+        // Values that FFmpeg doesn't know yet are skipped, instead of being plotted as zero.
+        self.fps.update(progress.fps);
+        if let Some(bitrate) = progress.bitrate {
+            self.bitrate.update(bitrate as f64);
+        }
+        if let Some(speed) = progress.speed {
+            self.speed.update(speed);
+        }
+        if let Some(q) = progress.quantizer() {
+            self.quantizer.update(q);
+        }
+
+        self.timestamp = timestamp;
+        self.progress = progress;
+    }
+
+    /// Add a resource usage sample, which is turned into rates over the time since the last one.
+    /// The process started together with the view, so the first sample covers all of that time.
+    fn update_usage(&mut self, timestamp: Duration, usage: Usage) {
+        let (earlier_timestamp, earlier) = self.usage.unwrap_or_default();
+        let rates = usage.rates(&earlier, timestamp - earlier_timestamp);
+
+        self.cpu.update(rates.cpu);
+        self.memory.update(usage.rss as f64);
+        self.read.update(rates.read);
+        self.write.update(rates.write);
+
+        self.usage = Some((timestamp, usage));
+    }
+
+    /// Position in the input. If the progress is tracked by the input, it's estimated from the
+    /// share of the file that was read so far.
+    fn position(&self) -> Duration {
+        match self.input_read.filter(|_| self.size > 0) {
+            Some(read) => self.duration * (read as f64 / self.size as f64).min(1.0),
+            None => self.progress.position(),
+        }
+    }
+
+    /// Remaining time of the encoding. If the progress is tracked by the input, it's projected from
+    /// the average rate at which the input was read, as the reported speed relates to the output
+    /// time instead.
+    fn eta(&self) -> Option<Duration> {
+        match self.input_read.filter(|_| self.size > 0) {
+            Some(read) => {
+                let rate = read as f64 / self.timestamp.as_seconds_f64();
+                (rate > 0.0)
+                    .then(|| Duration::seconds_f64(self.size.saturating_sub(read) as f64 / rate))
+            }
+            None => {
+                let speed = self.progress.speed.filter(|speed| *speed > 0.0)?;
+                Some((self.duration - self.position()) / speed)
+            }
+        }
+    }
+
+    /// Overall progress, combining all phases if there are several.
+    fn ratio(&self) -> f64 {
+        let ratio =
+            (self.position().as_seconds_f64() / self.duration.as_seconds_f64()).clamp(0.0, 1.0);
+
+        match self.phase {
+            Some(phase) => (phase.index as f64 + ratio) / phase.count as f64,
+            None => ratio,
+        }
+    }
+
+    fn draw(&self, f: &mut Frame<'_, impl Backend>, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(5), Constraint::Percentage(100)])
+            .split(area);
+
+        let lr = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(chunks[1]);
+
+        let left = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Length(6),
+                Constraint::Length(6),
+                Constraint::Percentage(100),
+            ])
+            .split(lr[0]);
+
+        let left_r1 = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Ratio(1, 3); 3])
+            .split(left[0]);
+
+        f.render_widget(
+            Gauge::default()
+                .block(
+                    Block::default()
+                        .title(Spans::from(
+                            [
+                                Some(Span::styled(
+                                    format!(
+                                        "{} / Run-time: {} / {} / {}",
+                                        self.phase.map_or_else(
+                                            || "Progress".to_owned(),
+                                            |p| format!(
+                                                "Pass {} of {}: {}",
+                                                p.index + 1,
+                                                p.count,
+                                                p.name
+                                            )
+                                        ),
+                                        format_duration(self.timestamp),
+                                        match self.input_read {
+                                            Some(read) => format!(
+                                                "Read: {} of {}",
+                                                format_size(read),
+                                                format_size(self.size)
+                                            ),
+                                            None => format!(
+                                                "Out-time: {}",
+                                                format_duration(self.progress.position())
+                                            ),
+                                        },
+                                        self.priority
+                                    ),
+                                    Style::default().fg(Color::Blue),
+                                )),
+                                self.warning.as_ref().map(|w| {
+                                    Span::styled(format!(" / {w}"), Style::default().fg(Color::Red))
+                                }),
+                            ]
+                            .into_iter()
+                            .flatten()
+                            .collect::<Vec<_>>(),
+                        ))
+                        .title_alignment(Alignment::Center)
+                        .borders(Borders::ALL)
+                        .border_type(BorderType::Rounded),
+                )
+                .gauge_style(Style::default().fg(Color::White).bg(Color::Black))
+                .ratio(self.ratio()),
+            chunks[0],
+        );
+
+        f.render_widget(
+            Paragraph::new(self.progress.frame.to_string()).block(
+                Block::default()
+                    .title(Span::styled("Frame", Style::default().fg(Color::Blue)))
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded),
+            ),
+            left_r1[0],
+        );
+        f.render_widget(
+            Paragraph::new(
+                self.progress
+                    .total_size
+                    .map_or_else(|| "N/A".to_owned(), format_size),
+            )
+            .block(
+                Block::default()
+                    .title(Span::styled("Total size", Style::default().fg(Color::Blue)))
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded),
+            ),
+            left_r1[1],
+        );
+        f.render_widget(
+            Paragraph::new(format!(
+                "{} / {}",
+                self.progress.dup_frames, self.progress.drop_frames
+            ))
+            .block(
+                Block::default()
+                    .title(Span::styled(
+                        "Dup / Drop frames",
+                        Style::default().fg(Color::Blue),
+                    ))
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded),
+            ),
+            left_r1[2],
+        );
+
+        f.render_widget(self.fps.create(left[1]), left[1]);
+        f.render_widget(self.speed.create(left[2]), left[2]);
+
+        if let Some(overlay) = self.fps.overlay(left[1]) {
+            f.render_widget(overlay, left[1]);
+        }
+        if let Some(overlay) = self.speed.overlay(left[2]) {
+            f.render_widget(overlay, left[2]);
+        }
+
+        // Only encoded video streams report a quantizer, so there's nothing to show for copies.
+        if self.progress.quantizer().is_some() {
+            f.render_widget(self.quantizer.create(left[3]), left[3]);
+
+            if let Some(overlay) = self.quantizer.overlay(left[3]) {
+                f.render_widget(overlay, left[3]);
             }
-            None => return Ok(history),
         }
 
-        fps.update(progress.fps);
-        bitrate.update(progress.bitrate as f64);
-        speed.update(progress.speed);
+        // Resources are only known on systems with `/proc`, so the chart keeps all space otherwise.
+        if self.usage.is_some() {
+            let right = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Min(0),
+                    Constraint::Length(6),
+                    Constraint::Length(6),
+                ])
+                .split(lr[1]);
+
+            f.render_widget(self.bitrate.create(), right[0]);
+
+            for (row, sparklines) in [[&self.cpu, &self.memory], [&self.read, &self.write]]
+                .into_iter()
+                .enumerate()
+            {
+                let columns = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Ratio(1, 2); 2])
+                    .split(right[row + 1]);
+
+                for (sparkline, area) in sparklines.into_iter().zip(columns) {
+                    f.render_widget(sparkline.create(area), area);
+
+                    if let Some(overlay) = sparkline.overlay(area) {
+                        f.render_widget(overlay, area);
+                    }
+                }
+            }
+        } else {
+            f.render_widget(self.bitrate.create(), lr[1]);
+        }
     }
 }
 
-fn show_stats(terminal: &mut Terminal<impl Backend>, stats: Stats) -> Result<()> {
-    let titles = ["Bitrate", "FPS", "Speed"]
+fn show_stats(
+    terminal: &mut Terminal<impl Backend>,
+    stats: Stats,
+    mut smoothing: Smoothing,
+) -> Result<()> {
+    let mut quality_stats = stats
+        .quality
+        .as_ref()
+        .map(|quality| quality_charts(quality, stats.import.duration))
+        .unwrap_or_default();
+    let mut metric = 0;
+    let mut quantizer_stats = quantizer_charts(&stats.history);
+    let mut stream = 0;
+
+    let tab_names = ["Bitrate", "FPS", "Speed"]
         .into_iter()
+        .chain((!quantizer_stats.is_empty()).then_some("Quantizer"))
+        .chain((!quality_stats.is_empty()).then_some("Quality"))
+        .chain((!stats.usage.is_empty()).then_some("CPU"))
+        .chain((!stats.usage.is_empty()).then_some("Memory"))
+        .chain((!stats.usage.is_empty()).then_some("Disk I/O"))
+        .collect::<Vec<_>>();
+    let titles = tab_names
+        .iter()
+        .copied()
         .map(Spans::from)
         .collect::<Vec<_>>();
     let mut selection = 0;
 
-    let bitrate_stats = BitrateStats::new(
+    let mut bitrate_stats = BitrateStats::new(
         stats.import.bit_rate as f64,
         stats
             .history
             .iter()
-            .map(|(d, p)| (d.as_seconds_f64(), p.bitrate as f64)),
+            .filter_map(|(d, p)| Some((d.as_seconds_f64(), p.bitrate? as f64))),
     );
-    let fps_stats = OneLineStats::new(
+    let mut fps_stats = OneLineStats::new(
         stats
             .history
             .iter()
             .map(|(d, p)| (d.as_seconds_f64(), p.fps)),
         |fps| format!("{fps:.1}"),
     );
-    let speed_stats = OneLineStats::new(
+    let mut speed_stats = OneLineStats::new(
         stats
             .history
             .iter()
-            .map(|(d, p)| (d.as_seconds_f64(), p.speed)),
+            .filter_map(|(d, p)| Some((d.as_seconds_f64(), p.speed?))),
         |speed| format!("{speed:.2}x"),
     );
+    let rates = stats
+        .usage
+        .iter()
+        .scan(<(Duration, Usage)>::default(), |earlier, &(d, usage)| {
+            let rates = usage.rates(&earlier.1, d - earlier.0);
+            *earlier = (d, usage);
+            Some((d.as_seconds_f64(), rates))
+        })
+        .collect::<Vec<_>>();
+    let mut cpu_stats = OneLineStats::new(rates.iter().map(|(d, rates)| (*d, rates.cpu)), |cpu| {
+        format!("{cpu:.0}%")
+    });
+    let mut memory_stats = OneLineStats::new(
+        stats
+            .usage
+            .iter()
+            .map(|(d, usage)| (d.as_seconds_f64(), usage.rss as f64)),
+        |rss| format_size(rss as u64),
+    );
+    let mut read_stats =
+        OneLineStats::new(rates.iter().map(|(d, rates)| (*d, rates.read)), |read| {
+            format!("{}/s", format_size(read as u64))
+        });
+    let mut write_stats =
+        OneLineStats::new(rates.iter().map(|(d, rates)| (*d, rates.write)), |write| {
+            format!("{}/s", format_size(write as u64))
+        });
+
+    // Quality scores are per frame, so the bitrate next to them is shown over the output time.
+    let mut quality_bitrate_stats = BitrateStats::new(
+        stats.import.bit_rate as f64,
+        stats
+            .history
+            .iter()
+            .filter_map(|(_, p)| Some((p.out_time?.as_seconds_f64(), p.bitrate? as f64))),
+    );
+
+    bitrate_stats.set_smoothing(smoothing);
+    fps_stats.set_smoothing(smoothing);
+    speed_stats.set_smoothing(smoothing);
+    cpu_stats.set_smoothing(smoothing);
+    memory_stats.set_smoothing(smoothing);
+    read_stats.set_smoothing(smoothing);
+    write_stats.set_smoothing(smoothing);
+    quality_bitrate_stats.set_smoothing(smoothing);
+    for (_, stats) in quality_stats.iter_mut().chain(&mut quantizer_stats) {
+        stats.set_smoothing(smoothing);
+    }
+
+    // Warnings about ignored progress values can be plenty, so they come last and the list is
+    // limited in height, to not push out the other events or the charts.
+    let (warnings, others) = stats
+        .events
+        .iter()
+        .partition::<Vec<_>, _>(|event| matches!(event, stats::Event::Warning { .. }));
+    let events = others
+        .into_iter()
+        .chain(warnings)
+        .map(|event| {
+            let color = match event {
+                _ if event.is_failure() => Color::Red,
+                stats::Event::Warning { .. } => Color::Yellow,
+                _ => Color::Green,
+            };
+            Spans::from(Span::styled(event.to_string(), Style::default().fg(color)))
+        })
+        .collect::<Vec<_>>();
+    let events_height = if events.is_empty() {
+        0
+    } else {
+        events.len().min(10) as u16 + 2
+    };
 
     terminal.draw(|f| f.render_widget(Clear, f.size()))?;
 
@@ -300,12 +2042,18 @@ fn show_stats(terminal: &mut Terminal<impl Backend>, stats: Stats) -> Result<()>
         terminal.draw(|f| {
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([Constraint::Length(3), Constraint::Percentage(100)])
+                .constraints([
+                    Constraint::Length(3),
+                    Constraint::Min(0),
+                    Constraint::Length(events_height),
+                ])
                 .split(f.size());
 
             let tabs = Tabs::new(titles.clone())
                 .block(
                     Block::default()
+                        .title(format!("Smoothing: {smoothing}"))
+                        .title_alignment(Alignment::Right)
                         .borders(Borders::ALL)
                         .border_type(BorderType::Rounded),
                 )
@@ -319,15 +2067,102 @@ fn show_stats(terminal: &mut Terminal<impl Backend>, stats: Stats) -> Result<()>
                 .divider("|")
                 .select(selection);
 
-            let chart = match selection {
-                0 => bitrate_stats.create(),
-                1 => fps_stats.create(),
-                2 => speed_stats.create(),
+            f.render_widget(tabs, chunks[0]);
+
+            match tab_names[selection] {
+                "Bitrate" => f.render_widget(bitrate_stats.create(chunks[1]), chunks[1]),
+                "FPS" => f.render_widget(fps_stats.create(chunks[1]), chunks[1]),
+                "Speed" => f.render_widget(speed_stats.create(chunks[1]), chunks[1]),
+                "CPU" => f.render_widget(cpu_stats.create(chunks[1]), chunks[1]),
+                "Memory" => f.render_widget(memory_stats.create(chunks[1]), chunks[1]),
+                "Disk I/O" => {
+                    let halves = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Ratio(1, 2); 2])
+                        .split(chunks[1]);
+
+                    for (title, stats, area) in [
+                        ("Read", &mut read_stats, halves[0]),
+                        ("Write", &mut write_stats, halves[1]),
+                    ] {
+                        f.render_widget(
+                            stats.create(area).block(
+                                Block::default()
+                                    .title(Span::styled(title, Style::default().fg(Color::Blue)))
+                                    .borders(Borders::ALL)
+                                    .border_type(BorderType::Rounded),
+                            ),
+                            area,
+                        );
+                    }
+                }
+                "Quantizer" => {
+                    let hint = if quantizer_stats.len() > 1 {
+                        " (m to switch)"
+                    } else {
+                        ""
+                    };
+                    let (title, stats) = &mut quantizer_stats[stream];
+
+                    f.render_widget(
+                        stats.create(chunks[1]).block(
+                            Block::default()
+                                .title(Span::styled(
+                                    format!("{title}{hint}"),
+                                    Style::default().fg(Color::Blue),
+                                ))
+                                .borders(Borders::ALL)
+                                .border_type(BorderType::Rounded),
+                        ),
+                        chunks[1],
+                    );
+                }
+                "Quality" => {
+                    let halves = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Ratio(1, 2); 2])
+                        .split(chunks[1]);
+                    let (title, stats) = &mut quality_stats[metric];
+
+                    f.render_widget(
+                        stats.create(halves[0]).block(
+                            Block::default()
+                                .title(Span::styled(
+                                    format!("{title} (m to switch)"),
+                                    Style::default().fg(Color::Blue),
+                                ))
+                                .borders(Borders::ALL)
+                                .border_type(BorderType::Rounded),
+                        ),
+                        halves[0],
+                    );
+                    f.render_widget(
+                        quality_bitrate_stats.create(halves[1]).block(
+                            Block::default()
+                                .title(Span::styled(
+                                    "Bitrate over output time",
+                                    Style::default().fg(Color::Blue),
+                                ))
+                                .borders(Borders::ALL)
+                                .border_type(BorderType::Rounded),
+                        ),
+                        halves[1],
+                    );
+                }
                 _ => unreachable!(),
-            };
+            }
 
-            f.render_widget(tabs, chunks[0]);
-            f.render_widget(chart, chunks[1]);
+            if !events.is_empty() {
+                f.render_widget(
+                    Paragraph::new(events.clone()).block(
+                        Block::default()
+                            .title(Span::styled("Events", Style::default().fg(Color::Blue)))
+                            .borders(Borders::ALL)
+                            .border_type(BorderType::Rounded),
+                    ),
+                    chunks[2],
+                );
+            }
         })?;
 
         if let Event::Key(event) = event::read()? {
@@ -337,16 +2172,85 @@ fn show_stats(terminal: &mut Terminal<impl Backend>, stats: Stats) -> Result<()>
                     return Ok(())
                 }
                 KeyCode::Left => selection = selection.saturating_sub(1),
-                KeyCode::Right => selection = 2.min(selection + 1),
+                KeyCode::Right => selection = (titles.len() - 1).min(selection + 1),
+                KeyCode::Char('m') => match tab_names[selection] {
+                    "Quality" => metric = (metric + 1) % quality_stats.len(),
+                    "Quantizer" => stream = (stream + 1) % quantizer_stats.len(),
+                    _ => {}
+                },
+                KeyCode::Char('s') => {
+                    smoothing = smoothing.next();
+                    bitrate_stats.set_smoothing(smoothing);
+                    fps_stats.set_smoothing(smoothing);
+                    speed_stats.set_smoothing(smoothing);
+                    cpu_stats.set_smoothing(smoothing);
+                    memory_stats.set_smoothing(smoothing);
+                    read_stats.set_smoothing(smoothing);
+                    write_stats.set_smoothing(smoothing);
+                    quality_bitrate_stats.set_smoothing(smoothing);
+                    for (_, stats) in quality_stats.iter_mut().chain(&mut quantizer_stats) {
+                        stats.set_smoothing(smoothing);
+                    }
+                }
                 _ => {}
             }
         }
     }
 }
 
+/// Create a chart for each measured quality metric, spreading the per-frame scores over the
+/// duration of the input. Each one is titled with the metric's name and mean score.
+fn quality_charts(quality: &Quality, duration: Duration) -> Vec<(String, OneLineStats)> {
+    quality
+        .metrics()
+        .map(|(name, scores)| {
+            let step = duration.as_seconds_f64() / scores.len() as f64;
+            let mean = scores.iter().sum::<f64>() / scores.len() as f64;
+            let precision = if name == "SSIM" { 4 } else { 2 };
+
+            (
+                format!("{name} / Mean: {mean:.precision$}"),
+                OneLineStats::new(
+                    scores
+                        .iter()
+                        .enumerate()
+                        .map(|(i, s)| (i as f64 * step, *s)),
+                    move |v| format!("{v:.precision$}"),
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Create a chart of the quantizer over time for each encoded stream, titled with the stream and its
+/// mean quantizer.
+fn quantizer_charts(history: &[(Duration, Progress)]) -> Vec<(String, OneLineStats)> {
+    let streams = history
+        .iter()
+        .flat_map(|(_, p)| p.quantizers.keys().copied())
+        .collect::<BTreeSet<_>>();
+
+    streams
+        .into_iter()
+        .map(|stream| {
+            let values = history
+                .iter()
+                .filter_map(|(d, p)| Some((d.as_seconds_f64(), *p.quantizers.get(&stream)?)))
+                .collect::<Vec<_>>();
+            let mean = values.iter().map(|(_, q)| q).sum::<f64>() / values.len() as f64;
+
+            (
+                format!("Stream {}:{} / Mean: {mean:.1}", stream.0, stream.1),
+                OneLineStats::new(values.into_iter(), |q| format!("{q:.1}")),
+            )
+        })
+        .collect()
+}
+
 struct BitrateStats {
     baseline_data: Vec<(f64, f64)>,
-    bitrate_data: Vec<(f64, f64)>,
+    bitrate_data: Series,
+    smoothed: Option<Series>,
     x_max: f64,
     x_labels: Vec<Span<'static>>,
     y_min: f64,
@@ -360,13 +2264,15 @@ impl BitrateStats {
         let mut y_min = f64::MAX;
         let mut y_max = 0.0_f64;
 
-        let bitrate_data = history
-            .inspect(|(duration, bitrate)| {
-                x_max = x_max.max(*duration);
-                y_min = y_min.min(*bitrate);
-                y_max = y_max.max(*bitrate);
-            })
-            .collect();
+        let bitrate_data = Series::new(
+            history
+                .inspect(|(duration, bitrate)| {
+                    x_max = x_max.max(*duration);
+                    y_min = y_min.min(*bitrate);
+                    y_max = y_max.max(*bitrate);
+                })
+                .collect(),
+        );
 
         let baseline_data = vec![(0.0, baseline), (x_max, baseline)];
 
@@ -395,6 +2301,7 @@ impl BitrateStats {
         Self {
             baseline_data,
             bitrate_data,
+            smoothed: None,
             x_max,
             x_labels,
             y_min,
@@ -403,20 +2310,25 @@ impl BitrateStats {
         }
     }
 
-    pub fn create(&self) -> Chart<'_> {
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.smoothed = smooth_series(&self.bitrate_data, smoothing);
+    }
+
+    pub fn create(&mut self, area: Rect) -> Chart<'_> {
         let baseline = Dataset::default()
             .marker(Marker::Block)
             .graph_type(GraphType::Line)
             .style(Style::default().fg(Color::Red))
             .data(&self.baseline_data);
 
-        let dataset = Dataset::default()
-            .marker(Marker::Braille)
-            .graph_type(GraphType::Line)
-            .style(Style::default().fg(Color::Blue))
-            .data(&self.bitrate_data);
+        let width = area.width.saturating_sub(2);
+        let mut datasets = vec![baseline];
+        datasets.extend(bucket_datasets(
+            self.bitrate_data.get(width),
+            self.smoothed.as_mut().map(|s| s.get(width)),
+        ));
 
-        Chart::new(vec![baseline, dataset])
+        Chart::new(datasets)
             .block(
                 Block::default()
                     .borders(Borders::ALL)
@@ -438,7 +2350,8 @@ impl BitrateStats {
 }
 
 struct OneLineStats {
-    data: Vec<(f64, f64)>,
+    data: Series,
+    smoothed: Option<Series>,
     x_max: f64,
     x_labels: Vec<Span<'static>>,
     y_min: f64,
@@ -455,13 +2368,15 @@ impl OneLineStats {
         let mut y_min = f64::MAX;
         let mut y_max = 0.0_f64;
 
-        let data = history
-            .inspect(|(duration, value)| {
-                x_max = x_max.max(*duration);
-                y_min = y_min.min(*value);
-                y_max = y_max.max(*value);
-            })
-            .collect();
+        let data = Series::new(
+            history
+                .inspect(|(duration, value)| {
+                    x_max = x_max.max(*duration);
+                    y_min = y_min.min(*value);
+                    y_max = y_max.max(*value);
+                })
+                .collect(),
+        );
 
         let x_labels = [0.0, x_max * 0.25, x_max * 0.50, x_max * 0.75, x_max]
             .into_iter()
@@ -485,6 +2400,7 @@ impl OneLineStats {
 
         Self {
             data,
+            smoothed: None,
             x_max,
             x_labels,
             y_min,
@@ -493,14 +2409,18 @@ impl OneLineStats {
         }
     }
 
-    pub fn create(&self) -> Chart<'_> {
-        let dataset = Dataset::default()
-            .marker(Marker::Braille)
-            .graph_type(GraphType::Line)
-            .style(Style::default().fg(Color::Blue))
-            .data(&self.data);
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.smoothed = smooth_series(&self.data, smoothing);
+    }
+
+    pub fn create(&mut self, area: Rect) -> Chart<'_> {
+        let width = area.width.saturating_sub(2);
+        let datasets = bucket_datasets(
+            self.data.get(width),
+            self.smoothed.as_mut().map(|s| s.get(width)),
+        );
 
-        Chart::new(vec![dataset])
+        Chart::new(datasets)
             .block(
                 Block::default()
                     .borders(Borders::ALL)
@@ -521,6 +2441,83 @@ impl OneLineStats {
     }
 }
 
+fn smooth_series(series: &Series, smoothing: Smoothing) -> Option<Series> {
+    match smoothing {
+        Smoothing::Off => None,
+        s => {
+            let data = series.data();
+            let smoothed = s.apply(data.iter().map(|(_, y)| *y));
+
+            Some(Series::new(
+                data.iter().map(|(x, _)| *x).zip(smoothed).collect(),
+            ))
+        }
+    }
+}
+
+/// Create the datasets for a downsampled series, with the min/max envelopes drawn below the mean
+/// line. The smoothed line, if present, is drawn on top of everything.
+fn bucket_datasets<'a>(buckets: &'a Buckets, smoothed: Option<&'a Buckets>) -> Vec<Dataset<'a>> {
+    let envelope = |data| {
+        Dataset::default()
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::DarkGray))
+            .data(data)
+    };
+
+    let mut datasets = Vec::with_capacity(4);
+
+    if !buckets.min.is_empty() {
+        datasets.push(envelope(&buckets.min));
+        datasets.push(envelope(&buckets.max));
+    }
+
+    datasets.push(
+        Dataset::default()
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Blue))
+            .data(&buckets.mean),
+    );
+
+    if let Some(smoothed) = smoothed {
+        datasets.push(
+            Dataset::default()
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::Yellow))
+                .data(&smoothed.mean),
+        );
+    }
+
+    datasets
+}
+
+/// Error returned when the user aborts the encoding.
+#[derive(Debug)]
+struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("encoding cancelled by user")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+fn format_size(size: u64) -> String {
+    if size > 1_000_000_000 {
+        format!("{:.2} GiB", size as f64 / 1_000_000_000.0)
+    } else if size > 1_000_000 {
+        format!("{:.2} MiB", size as f64 / 1_000_000.0)
+    } else if size > 1_000 {
+        format!("{:.2} KiB", size as f64 / 1_000.0)
+    } else {
+        format!("{size} B")
+    }
+}
+
 fn format_duration(d: Duration) -> String {
     let d = d.whole_seconds().abs();
     format!("{:02}:{:02}:{:02}", d / 3600, d / 60 % 60, d % 60)
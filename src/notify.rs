@@ -0,0 +1,270 @@
+use std::{
+    fmt::{self, Display},
+    io::{self, Write},
+    process::Command,
+};
+
+use anyhow::{ensure, Context, Result};
+use clap::ValueEnum;
+use time::Duration;
+
+use crate::{format_duration, Cancelled};
+
+/// Way of telling the user that an encoding is done.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Channel {
+    /// Ring the terminal bell and send an OSC 9 notification, which many terminals turn into a
+    /// desktop notification themselves.
+    Terminal,
+    /// Send a notification to the desktop, over the freedesktop notification interface of D-Bus.
+    Desktop,
+}
+
+/// How the encoding ended.
+pub enum Finish {
+    Completed,
+    Failed(String),
+    Cancelled,
+}
+
+impl Finish {
+    pub fn from_result(result: &Result<()>) -> Self {
+        match result {
+            Ok(()) => Self::Completed,
+            Err(e) if e.is::<Cancelled>() => Self::Cancelled,
+            Err(e) => Self::Failed(format!("{e:#}")),
+        }
+    }
+}
+
+/// Summary of a finished encoding, to be sent to the user.
+pub struct Notification {
+    /// What was encoded, like the input file name.
+    pub name: String,
+    pub finish: Finish,
+    /// Size of the output compared with the input, if known.
+    pub size_ratio: Option<f64>,
+    pub elapsed: Duration,
+}
+
+impl Notification {
+    pub fn summary(&self) -> String {
+        let state = match self.finish {
+            Finish::Completed => "finished",
+            Finish::Failed(_) => "failed",
+            Finish::Cancelled => "cancelled",
+        };
+        format!("{} {state}", self.name)
+    }
+
+    pub fn body(&self) -> String {
+        let mut body = format!("Elapsed: {}", format_duration(self.elapsed));
+        if let Some(ratio) = self.size_ratio {
+            body.push_str(&format!(", size: {:.1}% of the input", ratio * 100.0));
+        }
+        if let Finish::Failed(e) = &self.finish {
+            body.push_str(&format!("\n{}", e.lines().next().unwrap_or_default()));
+        }
+        body
+    }
+}
+
+impl Display for Notification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.summary(), self.body().replace('\n', ", "))
+    }
+}
+
+/// Something that can show a notification to the user.
+pub trait Notifier {
+    fn notify(&self, notification: &Notification) -> Result<()>;
+}
+
+/// Terminal bell, together with an OSC 9 escape sequence carrying the message.
+pub struct TerminalNotifier;
+
+impl TerminalNotifier {
+    fn write(mut out: impl Write, notification: &Notification) -> io::Result<()> {
+        // Control characters would end the escape sequence early.
+        let message = notification.to_string().replace(char::is_control, " ");
+        write!(out, "\x07\x1b]9;{message}\x07")?;
+        out.flush()
+    }
+}
+
+impl Notifier for TerminalNotifier {
+    fn notify(&self, notification: &Notification) -> Result<()> {
+        Self::write(io::stdout().lock(), notification).map_err(Into::into)
+    }
+}
+
+/// Desktop notification, sent over D-Bus with `gdbus`.
+pub struct DesktopNotifier;
+
+impl Notifier for DesktopNotifier {
+    fn notify(&self, notification: &Notification) -> Result<()> {
+        let output = Command::new("gdbus")
+            .args(notify_args(notification))
+            .output()
+            .context("failed running `gdbus` to send a desktop notification")?;
+
+        ensure!(
+            output.status.success(),
+            "failed sending a desktop notification: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+
+        Ok(())
+    }
+}
+
+/// Arguments for `gdbus`, to call the `Notify` method of the notification server.
+fn notify_args(notification: &Notification) -> Vec<String> {
+    [
+        "call",
+        "--session",
+        "--dest",
+        "org.freedesktop.Notifications",
+        "--object-path",
+        "/org/freedesktop/Notifications",
+        "--method",
+        "org.freedesktop.Notifications.Notify",
+    ]
+    .into_iter()
+    .map(ToOwned::to_owned)
+    .chain([
+        quote(env!("CARGO_PKG_NAME")),
+        "0".to_owned(),
+        quote(""),
+        quote(&notification.summary()),
+        quote(&notification.body()),
+        "[]".to_owned(),
+        "{}".to_owned(),
+        "-1".to_owned(),
+    ])
+    .collect()
+}
+
+/// Quote a string in the GVariant text format, that `gdbus` parses its arguments with.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Send the notification to all given notifiers. Failures are collected instead of stopping early,
+/// so one broken channel doesn't hide the others.
+pub fn send(notifiers: &[&dyn Notifier], notification: &Notification) -> Vec<anyhow::Error> {
+    notifiers
+        .iter()
+        .filter_map(|notifier| notifier.notify(notification).err())
+        .collect()
+}
+
+/// Create the notifiers for the given channels.
+pub fn notifiers(channels: &[Channel]) -> Vec<&'static dyn Notifier> {
+    channels
+        .iter()
+        .map(|channel| -> &'static dyn Notifier {
+            match channel {
+                Channel::Terminal => &TerminalNotifier,
+                Channel::Desktop => &DesktopNotifier,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use anyhow::{bail, Result};
+    use time::Duration;
+
+    use super::{Finish, Notification, Notifier, TerminalNotifier};
+
+    #[derive(Default)]
+    struct MockNotifier {
+        sent: RefCell<Vec<(String, String)>>,
+        fail: bool,
+    }
+
+    impl Notifier for MockNotifier {
+        fn notify(&self, notification: &Notification) -> Result<()> {
+            if self.fail {
+                bail!("no notification server");
+            }
+            self.sent
+                .borrow_mut()
+                .push((notification.summary(), notification.body()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn send_to_all_notifiers() {
+        let working = MockNotifier::default();
+        let broken = MockNotifier {
+            fail: true,
+            ..MockNotifier::default()
+        };
+
+        let completed = Notification {
+            name: "movie.mkv".to_owned(),
+            finish: Finish::Completed,
+            size_ratio: Some(0.425),
+            elapsed: Duration::seconds(3725),
+        };
+
+        let errors = super::send(&[&broken, &working], &completed);
+
+        assert_eq!(1, errors.len());
+        assert_eq!(
+            vec![(
+                "movie.mkv finished".to_owned(),
+                "Elapsed: 01:02:05, size: 42.5% of the input".to_owned()
+            )],
+            working.sent.into_inner()
+        );
+    }
+
+    #[test]
+    fn terminal_sequence() {
+        let mut out = Vec::new();
+        let failed = Notification {
+            name: "movie.mkv".to_owned(),
+            finish: Finish::Failed("exit code 1\nmore details".to_owned()),
+            size_ratio: Some(0.425),
+            elapsed: Duration::seconds(3725),
+        };
+
+        TerminalNotifier::write(&mut out, &failed).unwrap();
+
+        assert_eq!(
+            "\x07\x1b]9;movie.mkv failed: Elapsed: 01:02:05, size: 42.5% of the input, \
+             exit code 1\x07",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn desktop_args() {
+        let cancelled = Notification {
+            name: "it's.mkv".to_owned(),
+            finish: Finish::Cancelled,
+            size_ratio: None,
+            elapsed: Duration::seconds(3725),
+        };
+        let args = super::notify_args(&cancelled);
+
+        assert_eq!(
+            [
+                "'ffprog'",
+                "0",
+                "''",
+                "'it\\'s.mkv cancelled'",
+                "'Elapsed: 01:02:05'"
+            ]
+            .as_slice(),
+            &args[8..13]
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    fmt::{self, Display},
+    fs,
+    process::Command,
+    sync::Mutex,
+};
+#[cfg(target_os = "linux")]
+use std::{io, mem, os::unix::process::CommandExt};
+
+#[cfg(not(target_os = "linux"))]
+use anyhow::bail;
+use anyhow::{ensure, Context, Result};
+use clap::{Args, ValueEnum};
+
+/// Highest priority, as a nice value.
+const MIN_NICE: i32 = -20;
+/// Lowest priority, as a nice value.
+const MAX_NICE: i32 = 19;
+/// Amount of CPUs that an affinity can cover, being the size of a CPU set on Linux.
+const MAX_CPUS: usize = 1024;
+
+/// Scheduling settings of the FFmpeg processes, shared by all of them, so a niceness changed while
+/// running carries over to the next process of a batch or the second pass.
+static PRIORITY: Mutex<Priority> = Mutex::new(Priority {
+    nice: None,
+    io_class: None,
+    affinity: None,
+});
+
+/// Target of `ioprio_set`, being a single process or thread.
+#[cfg(target_os = "linux")]
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+/// Position of the class in an I/O priority, with the level in the bits below.
+#[cfg(target_os = "linux")]
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+
+/// I/O scheduling class, as understood by `ionice`.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum IoClass {
+    /// Only get disk time when no other process needs it.
+    Idle,
+    /// Default class, sharing disk time with other processes.
+    BestEffort,
+    /// Always get disk time first, which usually requires root permissions.
+    Realtime,
+}
+
+#[cfg(target_os = "linux")]
+impl IoClass {
+    /// I/O priority value for `ioprio_set`, with the default level of 4 for the classes that have
+    /// levels, like `ionice` does.
+    fn ioprio(self) -> libc::c_int {
+        match self {
+            Self::Idle => 3 << IOPRIO_CLASS_SHIFT,
+            Self::BestEffort => 2 << IOPRIO_CLASS_SHIFT | 4,
+            Self::Realtime => 1 << IOPRIO_CLASS_SHIFT | 4,
+        }
+    }
+}
+
+impl Display for IoClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Idle => "idle",
+            Self::BestEffort => "best-effort",
+            Self::Realtime => "realtime",
+        })
+    }
+}
+
+/// Process priority and CPU affinity, to keep the system responsive during long encodings. These
+/// are applied with Linux specific system calls, so only work on Linux.
+#[derive(Args, Clone, Default)]
+pub struct Priority {
+    /// Nice value of FFmpeg, from -20 (highest priority) to 19 (lowest priority). Can be raised
+    /// and lowered with the `+` and `-` keys while running.
+    #[arg(
+        long,
+        global = true,
+        allow_hyphen_values = true,
+        value_parser = clap::value_parser!(i32).range(i64::from(MIN_NICE)..=i64::from(MAX_NICE))
+    )]
+    pub nice: Option<i32>,
+    /// I/O scheduling class of FFmpeg.
+    #[arg(long, global = true, value_enum)]
+    pub io_class: Option<IoClass>,
+    /// CPUs that FFmpeg may run on, as a list like `0-3,6`.
+    #[arg(long, global = true, value_parser = parse_affinity)]
+    pub affinity: Option<String>,
+}
+
+impl Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.nice {
+            Some(nice) => write!(f, "Nice: {nice}")?,
+            None => f.write_str("Nice: ?")?,
+        }
+        if let Some(class) = self.io_class {
+            write!(f, ", I/O: {class}")?;
+        }
+        if let Some(affinity) = &self.affinity {
+            write!(f, ", CPUs: {affinity}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Set the scheduling settings for all following FFmpeg processes. Other systems than Linux don't
+/// support any of them.
+pub fn init(priority: Priority) -> Result<()> {
+    #[cfg(not(target_os = "linux"))]
+    ensure!(
+        priority.nice.is_none() && priority.io_class.is_none() && priority.affinity.is_none(),
+        "`--nice`, `--io-class` and `--affinity` are only supported on Linux"
+    );
+
+    *PRIORITY.lock().expect("priority is never poisoned") = priority;
+    Ok(())
+}
+
+/// Current scheduling settings. Without an explicit nice value, the processes inherit the one of
+/// this process.
+pub fn current() -> Priority {
+    let mut priority = PRIORITY.lock().expect("priority is never poisoned").clone();
+    priority.nice = priority.nice.or_else(|| read_nice("self"));
+    priority
+}
+
+/// Apply the scheduling settings to the command. They're set in the new process right before it
+/// executes FFmpeg, so all threads that it starts later inherit them. Failing to set them fails
+/// spawning the command.
+#[cfg(target_os = "linux")]
+pub fn apply(cmd: &mut Command) -> Result<()> {
+    let priority = PRIORITY.lock().expect("priority is never poisoned").clone();
+    let Priority {
+        nice,
+        io_class,
+        affinity,
+    } = priority;
+    let cpus = affinity.as_deref().map(cpu_set).transpose()?;
+
+    if nice.is_none() && io_class.is_none() && cpus.is_none() {
+        return Ok(());
+    }
+
+    // SAFETY: The closure runs between fork and exec, and only makes system calls, without
+    // allocating or taking any locks.
+    unsafe {
+        cmd.pre_exec(move || {
+            if let Some(nice) = nice {
+                check(libc::setpriority(libc::PRIO_PROCESS, 0, nice))?;
+            }
+            if let Some(class) = io_class {
+                check(libc::syscall(
+                    libc::SYS_ioprio_set,
+                    IOPRIO_WHO_PROCESS,
+                    0,
+                    class.ioprio(),
+                ))?;
+            }
+            if let Some(cpus) = &cpus {
+                check(libc::sched_setaffinity(
+                    0,
+                    mem::size_of::<libc::cpu_set_t>(),
+                    cpus,
+                ))?;
+            }
+            Ok(())
+        });
+    }
+
+    Ok(())
+}
+
+/// Scheduling settings are only supported on Linux, so there is nothing to apply elsewhere.
+#[cfg(not(target_os = "linux"))]
+pub fn apply(_cmd: &mut Command) -> Result<()> {
+    Ok(())
+}
+
+/// Change the nice value of the given processes by the delta, and keep it for following
+/// processes. Lowering the value below the initial one usually requires root permissions.
+#[cfg(target_os = "linux")]
+pub fn adjust(pids: &[u32], delta: i32) -> Result<Priority> {
+    let current = current();
+    let nice = (current.nice.unwrap_or_default() + delta).clamp(MIN_NICE, MAX_NICE);
+
+    for task in pids.iter().flat_map(|&pid| tasks(pid)) {
+        // SAFETY: Plain system call, that only reads its arguments.
+        match check(unsafe { libc::setpriority(libc::PRIO_PROCESS, task, nice) }) {
+            // The thread or process ended in the meantime.
+            Err(e) if e.raw_os_error() == Some(libc::ESRCH) => {}
+            res => res.context("failed setting the nice value")?,
+        }
+    }
+
+    PRIORITY.lock().expect("priority is never poisoned").nice = Some(nice);
+    Ok(Priority {
+        nice: Some(nice),
+        ..current
+    })
+}
+
+/// Changing the nice value is only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn adjust(_pids: &[u32], _delta: i32) -> Result<Priority> {
+    bail!("changing the niceness is only supported on Linux")
+}
+
+/// IDs of all threads of the process. Priorities on Linux are set per thread, and FFmpeg encodes
+/// with many of them.
+#[cfg(target_os = "linux")]
+fn tasks(pid: u32) -> Vec<libc::id_t> {
+    let tasks = fs::read_dir(format!("/proc/{pid}/task"))
+        .map(|dir| {
+            dir.filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    if tasks.is_empty() {
+        vec![pid]
+    } else {
+        tasks
+    }
+}
+
+/// Read the nice value of a process from its `stat` file.
+fn read_nice(pid: &str) -> Option<i32> {
+    parse_nice(&fs::read_to_string(format!("/proc/{pid}/stat")).ok()?)
+}
+
+/// Parse the nice value from the content of a `/proc/<pid>/stat` file. It's the 19th field, but
+/// as the process name in the 2nd field may contain spaces, counting starts after it.
+fn parse_nice(stat: &str) -> Option<i32> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    rest.split_whitespace().nth(16)?.parse().ok()
+}
+
+/// Turn the return value of a system call into an error, if it failed.
+#[cfg(target_os = "linux")]
+fn check(ret: impl Into<i64>) -> io::Result<()> {
+    if ret.into() == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Parse a CPU list in the format of `taskset`, like `0-3,6`.
+pub fn parse_affinity(value: &str) -> Result<String> {
+    cpus(value)?;
+    Ok(value.to_owned())
+}
+
+/// Parse a CPU list into the ranges of CPUs it contains.
+fn cpus(value: &str) -> Result<Vec<(usize, usize)>> {
+    value
+        .split(',')
+        .map(|part| {
+            let (start, end) = part.split_once('-').unwrap_or((part, part));
+            let start = start
+                .parse::<usize>()
+                .context("CPUs must be numbers, like `0-3,6`")?;
+            let end = end
+                .parse::<usize>()
+                .context("CPUs must be numbers, like `0-3,6`")?;
+            ensure!(start <= end, "CPU range `{part}` is reversed");
+            ensure!(
+                end < MAX_CPUS,
+                "CPU {end} is out of range, the highest one is {}",
+                MAX_CPUS - 1
+            );
+            Ok((start, end))
+        })
+        .collect()
+}
+
+/// Create the CPU set for `sched_setaffinity` from a CPU list.
+#[cfg(target_os = "linux")]
+fn cpu_set(value: &str) -> Result<libc::cpu_set_t> {
+    const _: () = assert!(MAX_CPUS <= libc::CPU_SETSIZE as usize);
+
+    // SAFETY: The set is a plain bit mask, for which all zeros is the empty set.
+    let mut set = unsafe { mem::zeroed::<libc::cpu_set_t>() };
+
+    for (start, end) in cpus(value)? {
+        for cpu in start..=end {
+            // SAFETY: The CPU was checked to be within `MAX_CPUS`, the size of the set.
+            unsafe { libc::CPU_SET(cpu, &mut set) };
+        }
+    }
+
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_stat() {
+        let stat = "4242 (ffmpeg (x) 1) S 1 4242 4242 0 -1 4194560 5243 0 0 0 120 15 0 0 30 10 17 \
+                    0 104553 2451554304 20000 18446744073709551615";
+
+        assert_eq!(Some(10), super::parse_nice(stat));
+        assert_eq!(None, super::parse_nice("4242 ffmpeg"));
+    }
+
+    #[test]
+    fn parse_cpu_lists() {
+        assert_eq!("0-3,6", super::parse_affinity("0-3,6").unwrap());
+        assert!(super::parse_affinity("3-0").is_err());
+        assert!(super::parse_affinity("0,,1").is_err());
+        assert!(super::parse_affinity("all").is_err());
+        assert!(super::parse_affinity("0-4096").is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::ffmpeg::{self, ProgressIter};
+
+/// Upper limit for PSNR values, which are infinite for identical frames.
+const MAX_PSNR: f64 = 100.0;
+
+/// Per-frame quality scores of an encoding, compared to its input. Metrics that weren't measured
+/// are left empty.
+#[derive(Clone, Default)]
+pub struct Quality {
+    pub ssim: Vec<f64>,
+    pub psnr: Vec<f64>,
+    pub vmaf: Vec<f64>,
+}
+
+impl Quality {
+    /// All measured metrics, together with their name.
+    pub fn metrics(&self) -> impl Iterator<Item = (&'static str, &[f64])> {
+        [
+            ("VMAF", self.vmaf.as_slice()),
+            ("SSIM", self.ssim.as_slice()),
+            ("PSNR", self.psnr.as_slice()),
+        ]
+        .into_iter()
+        .filter(|(_, scores)| !scores.is_empty())
+    }
+}
+
+/// Running quality analysis, that compares the output with the input. The filters write their
+/// per-frame scores into a temporary directory, which is read once FFmpeg is done.
+pub struct Analysis {
+    dir: PathBuf,
+    vmaf: bool,
+}
+
+impl Analysis {
+    /// Start the analysis, including VMAF if FFmpeg was built with it.
+    pub fn spawn(output: &Path, input: &Path, stats_period: f64) -> Result<(Self, ProgressIter)> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = std::env::temp_dir().join(format!(
+            "{}-quality-{}-{}",
+            env!("CARGO_PKG_NAME"),
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir)?;
+
+        let analysis = Self {
+            dir,
+            vmaf: ffmpeg::has_filter("libvmaf"),
+        };
+
+        // FFmpeg runs inside the temporary directory, to avoid escaping its path in the filter.
+        let output = fs::canonicalize(output)?;
+        let input = fs::canonicalize(input)?;
+        let ffmpeg = ffmpeg::spawn_analysis(
+            &[&output, &input],
+            Some(&filter(analysis.vmaf)),
+            Some(&analysis.dir),
+            stats_period,
+        )?;
+
+        Ok((analysis, ffmpeg))
+    }
+
+    /// Read the scores, once FFmpeg finished successfully.
+    pub fn finish(self) -> Result<Quality> {
+        let read = |name: &str| {
+            fs::read_to_string(self.dir.join(name))
+                .with_context(|| format!("failed reading the {name} scores"))
+        };
+
+        Ok(Quality {
+            ssim: parse_scores(&read("ssim.log")?, "All:", 1.0),
+            psnr: parse_scores(&read("psnr.log")?, "psnr_avg:", MAX_PSNR),
+            vmaf: if self.vmaf {
+                parse_vmaf(&read("vmaf.json")?)?
+            } else {
+                Vec::new()
+            },
+        })
+    }
+}
+
+impl Drop for Analysis {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+/// Build the filter graph, which scales the output to the input's size and then feeds both into
+/// each of the metric filters.
+fn filter(vmaf: bool) -> String {
+    let count = if vmaf { 3 } else { 2 };
+    let pads = |prefix: char| {
+        (0..count)
+            .map(|i| format!("[{prefix}{i}]"))
+            .collect::<String>()
+    };
+
+    let mut filter = format!(
+        "[0:v][1:v]scale2ref[dist][ref];[dist]split={count}{};[ref]split={count}{};\
+         [d0][r0]ssim=stats_file=ssim.log;[d1][r1]psnr=stats_file=psnr.log",
+        pads('d'),
+        pads('r'),
+    );
+
+    if vmaf {
+        filter.push_str(";[d2][r2]libvmaf=log_fmt=json:log_path=vmaf.json");
+    }
+
+    filter
+}
+
+/// Parse the stats file of the `ssim` or `psnr` filter, taking the value of the given key from
+/// each line. Infinite values are capped at the limit.
+fn parse_scores(content: &str, key: &str, limit: f64) -> Vec<f64> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let value = line
+                .split_whitespace()
+                .find_map(|field| field.strip_prefix(key))?;
+
+            match value {
+                "inf" => Some(limit),
+                _ => value.parse::<f64>().ok().map(|v| v.min(limit)),
+            }
+        })
+        .collect()
+}
+
+fn parse_vmaf(content: &str) -> Result<Vec<f64>> {
+    #[derive(Deserialize)]
+    struct Log {
+        frames: Vec<Frame>,
+    }
+
+    #[derive(Deserialize)]
+    struct Frame {
+        metrics: Metrics,
+    }
+
+    #[derive(Deserialize)]
+    struct Metrics {
+        vmaf: f64,
+    }
+
+    let log = serde_json::from_str::<Log>(content).context("invalid VMAF log")?;
+
+    Ok(log.frames.into_iter().map(|f| f.metrics.vmaf).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_ssim_and_psnr() {
+        let ssim = "n:1 Y:0.995 U:0.998 V:0.997 All:0.996 (23.98)\n\
+                    n:2 Y:0.990 U:0.995 V:0.994 All:0.992 (20.96)\n";
+        let psnr = "n:1 mse_avg:0.00 mse_y:0.00 psnr_avg:inf psnr_y:inf\n\
+                    n:2 mse_avg:1.25 mse_y:1.40 psnr_avg:47.16 psnr_y:46.67\n";
+
+        assert_eq!(vec![0.996, 0.992], super::parse_scores(ssim, "All:", 1.0));
+        assert_eq!(
+            vec![100.0, 47.16],
+            super::parse_scores(psnr, "psnr_avg:", 100.0)
+        );
+    }
+
+    #[test]
+    fn parse_vmaf() {
+        let log = r#"{"version":"2.3.1","frames":[
+            {"frameNum":0,"metrics":{"integer_motion":0.0,"vmaf":97.4}},
+            {"frameNum":1,"metrics":{"integer_motion":1.2,"vmaf":95.1}}
+        ]}"#;
+
+        assert_eq!(vec![97.4, 95.1], super::parse_vmaf(log).unwrap());
+    }
+
+    #[test]
+    fn filter_graph() {
+        assert_eq!(
+            "[0:v][1:v]scale2ref[dist][ref];[dist]split=2[d0][d1];[ref]split=2[r0][r1];\
+             [d0][r0]ssim=stats_file=ssim.log;[d1][r1]psnr=stats_file=psnr.log",
+            super::filter(false)
+        );
+        assert!(super::filter(true).ends_with("[d2][r2]libvmaf=log_fmt=json:log_path=vmaf.json"));
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{anyhow, Result};
+use time::{Duration, Instant};
+
+use crate::{
+    ffmpeg::{Progress, ProgressIter, ProgressSource},
+    priority::{self, Priority},
+    usage::Usage,
+};
+
+/// Update from one of the jobs, running in the background.
+pub enum Message {
+    Progress {
+        job: usize,
+        timestamp: Duration,
+        progress: Box<Progress>,
+        /// Resource usage of the process, if it could be sampled.
+        usage: Option<Usage>,
+        /// Bytes of the input that were read, if progress is tracked by the input.
+        input_read: Option<u64>,
+        /// Progress values that were ignored since the last update.
+        warnings: Vec<String>,
+        /// Total amount of progress values that were ignored so far.
+        ignored: usize,
+    },
+    Finished {
+        job: usize,
+        result: Result<()>,
+        /// Everything FFmpeg logged while running.
+        log: Vec<String>,
+    },
+}
+
+/// Runner for several FFmpeg processes at once. Each [`ProgressIter`] is read on its own thread
+/// and all updates are collected in a single channel.
+pub struct Runner {
+    limit: usize,
+    source: ProgressSource,
+    running: usize,
+    tx: Sender<Message>,
+    rx: Receiver<Message>,
+    stops: Vec<Arc<AtomicBool>>,
+    /// Process IDs of the running jobs.
+    pids: Vec<Option<u32>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Runner {
+    /// Create a new runner, that allows up to `limit` jobs to run concurrently.
+    pub fn new(limit: usize, source: ProgressSource) -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        Self {
+            limit,
+            source,
+            running: 0,
+            tx,
+            rx,
+            stops: Vec::new(),
+            pids: Vec::new(),
+            threads: Vec::new(),
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.running >= self.limit
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.running == 0
+    }
+
+    pub fn start(&mut self, job: usize, ffmpeg: ProgressIter) {
+        let tx = self.tx.clone();
+        let stop = Arc::<AtomicBool>::default();
+        let source = self.source;
+
+        if self.stops.len() <= job {
+            self.stops.resize_with(job + 1, Arc::default);
+        }
+        self.stops[job] = Arc::clone(&stop);
+
+        if self.pids.len() <= job {
+            self.pids.resize(job + 1, None);
+        }
+        self.pids[job] = ffmpeg.id();
+
+        self.running += 1;
+        self.threads
+            .push(thread::spawn(move || read(job, ffmpeg, source, &tx, &stop)));
+    }
+
+    /// Stop a single job early. It'll still report back as finished, with an error.
+    pub fn stop(&self, job: usize) {
+        if let Some(stop) = self.stops.get(job) {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Raise or lower the nice value of all running jobs.
+    pub fn adjust_nice(&self, delta: i32) -> Result<Priority> {
+        let pids = self.pids.iter().flatten().copied().collect::<Vec<_>>();
+        priority::adjust(&pids, delta)
+    }
+
+    /// Wait for the next update from any of the jobs, up to the given timeout.
+    pub fn recv(&mut self, timeout: std::time::Duration) -> Option<Message> {
+        let msg = self.rx.recv_timeout(timeout).ok()?;
+
+        if let Message::Finished { job, .. } = msg {
+            self.running -= 1;
+            self.pids[job] = None;
+        }
+
+        Some(msg)
+    }
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        // Each thread checks for cancellation after every progress report, and kills its FFmpeg
+        // process by dropping the iterator. Waiting for them ensures no orphans are left behind.
+        for stop in &self.stops {
+            stop.store(true, Ordering::Relaxed);
+        }
+
+        for thread in self.threads.drain(..) {
+            thread.join().ok();
+        }
+    }
+}
+
+fn read(
+    job: usize,
+    mut ffmpeg: ProgressIter,
+    source: ProgressSource,
+    tx: &Sender<Message>,
+    stop: &AtomicBool,
+) {
+    let start_time = Instant::now();
+
+    let result = loop {
+        if stop.load(Ordering::Relaxed) {
+            break Err(anyhow!("job stopped"));
+        }
+
+        match ffmpeg.next() {
+            Some(Ok(progress)) => {
+                let msg = Message::Progress {
+                    job,
+                    timestamp: start_time.elapsed(),
+                    progress: Box::new(progress),
+                    usage: ffmpeg.usage(),
+                    input_read: ffmpeg.input_read(source),
+                    warnings: ffmpeg.take_warnings(),
+                    ignored: ffmpeg.ignored(),
+                };
+
+                if tx.send(msg).is_err() {
+                    return;
+                }
+            }
+            Some(Err(e)) => break Err(e),
+            None => break Ok(()),
+        }
+    };
+
+    // Drop the iterator first, so the process is gone once the job is reported as finished.
+    let log = ffmpeg.log();
+    drop(ffmpeg);
+    tx.send(Message::Finished { job, result, log }).ok();
+}
@@ -0,0 +1,197 @@
+use time::Duration;
+use tui::{
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Paragraph},
+};
+
+use crate::{format_size, stats::Event, Header};
+
+/// Goal of a CRF search, that the projected full encoding must meet.
+#[derive(Clone, Copy)]
+pub enum Target {
+    /// Minimum mean VMAF score over all samples.
+    Vmaf(f64),
+    /// Maximum size of the full output, in bytes.
+    Size(u64),
+}
+
+impl Target {
+    fn is_met(self, vmaf: Option<f64>, size: u64) -> bool {
+        match self {
+            Self::Vmaf(target) => vmaf.is_some_and(|vmaf| vmaf >= target),
+            Self::Size(max) => size <= max,
+        }
+    }
+}
+
+/// Result of encoding the samples with a single CRF value.
+pub struct Attempt {
+    pub crf: u8,
+    pub vmaf: Option<f64>,
+    pub projected_size: u64,
+    pub passed: bool,
+}
+
+impl From<&Attempt> for Event {
+    fn from(a: &Attempt) -> Self {
+        Self::CrfAttempt {
+            crf: a.crf,
+            vmaf: a.vmaf,
+            projected_size: a.projected_size,
+            passed: a.passed,
+        }
+    }
+}
+
+/// Bisection over a range of CRF values, looking for the one that just meets the target. For a
+/// VMAF target that's the highest CRF that still reaches the score, giving the smallest file. For
+/// a size target it's the lowest CRF that still fits, giving the best quality.
+pub struct Search {
+    target: Target,
+    low: u8,
+    high: u8,
+    best: Option<u8>,
+    attempts: Vec<Attempt>,
+}
+
+impl Search {
+    pub fn new(target: Target, min: u8, max: u8) -> Self {
+        Self {
+            target,
+            low: min,
+            high: max,
+            best: None,
+            attempts: Vec::new(),
+        }
+    }
+
+    /// The next CRF value to try, or `None` once the search is done.
+    pub fn next(&self) -> Option<u8> {
+        (self.low <= self.high).then(|| self.low + (self.high - self.low) / 2)
+    }
+
+    /// Record the result of trying the given CRF value, narrowing down the search range.
+    pub fn record(&mut self, crf: u8, vmaf: Option<f64>, projected_size: u64) {
+        let passed = self.target.is_met(vmaf, projected_size);
+
+        // Higher CRF values lower both the quality and the size.
+        let go_higher = match self.target {
+            Target::Vmaf(_) => passed,
+            Target::Size(_) => !passed,
+        };
+
+        if passed {
+            self.best = Some(crf);
+        }
+        if go_higher {
+            self.low = crf + 1;
+        } else if crf == 0 {
+            self.low = self.high + 1;
+        } else {
+            self.high = crf - 1;
+        }
+
+        self.attempts.push(Attempt {
+            crf,
+            vmaf,
+            projected_size,
+            passed,
+        });
+    }
+
+    /// The chosen CRF value, if any of the attempts met the target.
+    pub fn result(&self) -> Option<u8> {
+        self.best
+    }
+
+    pub fn attempts(&self) -> &[Attempt] {
+        &self.attempts
+    }
+}
+
+impl Header for Search {
+    fn create(&self, _in_progress: Duration, title: &str) -> Paragraph<'_> {
+        let label = Style::default().fg(Color::Blue);
+        let mut spans = vec![Span::styled(
+            match self.target {
+                Target::Vmaf(target) => format!("Target: VMAF {target:.1}"),
+                Target::Size(max) => format!("Target: {}", format_size(max)),
+            },
+            label,
+        )];
+
+        for attempt in &self.attempts {
+            spans.push(Span::raw("  "));
+            spans.push(Span::styled(
+                format!(
+                    "CRF {}: {}",
+                    attempt.crf,
+                    match attempt.vmaf {
+                        Some(vmaf) => format!("{vmaf:.1}"),
+                        None => format_size(attempt.projected_size),
+                    }
+                ),
+                Style::default().fg(if attempt.passed {
+                    Color::Green
+                } else {
+                    Color::Red
+                }),
+            ));
+        }
+
+        Paragraph::new(Spans::from(spans)).block(
+            Block::default()
+                .title(Span::styled(format!("CRF search / {title}"), label))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded),
+        )
+    }
+}
+
+/// Fill the `{crf}` placeholder of an argument template with the given value.
+pub fn fill_crf(template: &[String], crf: u8) -> Vec<String> {
+    template
+        .iter()
+        .map(|arg| arg.replace("{crf}", &crf.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Search, Target};
+
+    #[test]
+    fn bisect_vmaf() {
+        let mut search = Search::new(Target::Vmaf(95.0), 10, 40);
+        let mut tried = Vec::new();
+
+        // Quality drops by one point per CRF step, so CRF 23 is the last one to reach the target.
+        while let Some(crf) = search.next() {
+            tried.push(crf);
+            search.record(crf, Some(118.0 - f64::from(crf)), 0);
+        }
+
+        assert_eq!(Some(23), search.result());
+        assert_eq!(vec![25, 17, 21, 23, 24], tried);
+    }
+
+    #[test]
+    fn bisect_size() {
+        let mut search = Search::new(Target::Size(1000), 0, 51);
+
+        while let Some(crf) = search.next() {
+            search.record(crf, None, 6000 - u64::from(crf) * 100);
+        }
+
+        assert_eq!(Some(50), search.result());
+
+        let mut search = Search::new(Target::Size(10), 0, 51);
+
+        while let Some(crf) = search.next() {
+            search.record(crf, None, 6000 - u64::from(crf) * 100);
+        }
+
+        assert_eq!(None, search.result());
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
     collections::BTreeMap,
+    fmt::{self, Display},
     fs::File,
     io::{BufReader, BufWriter, Write},
     path::{Path, PathBuf},
@@ -10,11 +11,166 @@ use bincode::{config, BorrowDecode, Decode, Encode};
 use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use time::Duration;
 
-use crate::{ffmpeg::Progress, ffprobe::Format};
+use crate::{ffmpeg::Progress, ffprobe::Format, quality::Quality, usage::Usage};
 
 pub struct Stats {
     pub import: Format,
     pub history: Vec<(Duration, Progress)>,
+    pub events: Vec<Event>,
+    /// Per-frame quality scores, if the output was analyzed.
+    pub quality: Option<Quality>,
+    /// History of the analysis pass of a two-pass encoding, empty for single-pass ones.
+    pub first_pass: Vec<(Duration, Progress)>,
+    /// Resource usage of the FFmpeg process, empty if it couldn't be sampled.
+    pub usage: Vec<(Duration, Usage)>,
+}
+
+impl Stats {
+    /// The event that caused the encoding to count as failed, if any.
+    pub fn failure(&self) -> Option<&Event> {
+        self.events.iter().find(|e| e.is_failure())
+    }
+}
+
+/// Notable occurrence during the encoding, kept together with the statistics.
+#[derive(Clone)]
+pub enum Event {
+    /// The projected output size exceeded the allowed ratio of the input size.
+    SizeGuard {
+        timestamp: Duration,
+        projected: u64,
+        limit: u64,
+        aborted: bool,
+    },
+    /// The sizes of input and output were compared, to only keep the smaller file.
+    KeepSmaller {
+        input_size: u64,
+        output_size: u64,
+        kept_output: bool,
+        dry_run: bool,
+    },
+    /// The output was probed and compared with the input, listing any problems found.
+    Verified { problems: Vec<String> },
+    /// The output was fully decoded, counting all errors and keeping the first few of them.
+    Integrity { errors: u64, messages: Vec<String> },
+    /// Samples were encoded with a CRF value during a CRF search, checking it against the target.
+    CrfAttempt {
+        crf: u8,
+        vmaf: Option<f64>,
+        projected_size: u64,
+        passed: bool,
+    },
+    /// An invalid value in the progress output of ffmpeg was ignored.
+    Warning {
+        timestamp: Duration,
+        message: String,
+    },
+}
+
+impl Event {
+    /// Maximum amount of decode errors kept in an integrity event.
+    const MAX_MESSAGES: usize = 20;
+
+    /// Create an integrity event from the log of a decode check, where every line is an error.
+    pub fn integrity(log: Vec<String>) -> Self {
+        let messages = log
+            .into_iter()
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>();
+
+        Self::Integrity {
+            errors: messages.len() as u64,
+            messages: messages.into_iter().take(Self::MAX_MESSAGES).collect(),
+        }
+    }
+
+    /// Create warning events for the progress values that were ignored at the given time.
+    pub fn warnings(timestamp: Duration, messages: Vec<String>) -> impl Iterator<Item = Self> {
+        messages
+            .into_iter()
+            .map(move |message| Self::Warning { timestamp, message })
+    }
+
+    /// Whether this event is from the size guard.
+    pub fn is_size_guard(&self) -> bool {
+        matches!(self, Self::SizeGuard { .. })
+    }
+
+    /// Whether the encoding was stopped early because of this event.
+    pub fn is_abort(&self) -> bool {
+        match self {
+            Self::SizeGuard { aborted, .. } => *aborted,
+            Self::KeepSmaller { .. }
+            | Self::Verified { .. }
+            | Self::Integrity { .. }
+            | Self::CrfAttempt { .. }
+            | Self::Warning { .. } => false,
+        }
+    }
+
+    /// Whether the encoding counts as failed because of this event.
+    pub fn is_failure(&self) -> bool {
+        match self {
+            Self::Verified { problems } => !problems.is_empty(),
+            Self::Integrity { errors, .. } => *errors > 0,
+            _ => self.is_abort(),
+        }
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SizeGuard {
+                projected,
+                limit,
+                aborted,
+                ..
+            } => write!(
+                f,
+                "projected output size of {} exceeds the limit of {}{}",
+                crate::format_size(*projected),
+                crate::format_size(*limit),
+                if *aborted { ", encoding aborted" } else { "" }
+            ),
+            Self::KeepSmaller {
+                input_size,
+                output_size,
+                kept_output,
+                dry_run,
+            } => write!(
+                f,
+                "{} the {} ({} input, {} output)",
+                if *dry_run { "would keep" } else { "kept" },
+                if *kept_output { "output" } else { "input" },
+                crate::format_size(*input_size),
+                crate::format_size(*output_size),
+            ),
+            Self::Verified { problems } if problems.is_empty() => f.write_str("output verified"),
+            Self::Verified { problems } => {
+                write!(f, "output verification failed: {}", problems.join(", "))
+            }
+            Self::Integrity { errors: 0, .. } => f.write_str("output decoded without errors"),
+            Self::Integrity { errors, messages } => write!(
+                f,
+                "found {errors} decode errors in the output, first: {}",
+                messages.first().map_or("", |m| m.trim())
+            ),
+            Self::CrfAttempt {
+                crf,
+                vmaf,
+                projected_size,
+                passed,
+            } => write!(
+                f,
+                "CRF {crf}: {}projected size of {}, {} the target",
+                vmaf.map(|v| format!("VMAF {v:.2}, ")).unwrap_or_default(),
+                crate::format_size(*projected_size),
+                if *passed { "meets" } else { "misses" }
+            ),
+            Self::Warning { message, .. } => f.write_str(message),
+        }
+    }
 }
 
 #[derive(Encode, Decode)]
@@ -23,11 +179,19 @@ enum Version {
         import: FormatV1,
         history: Vec<(BincodeDuration, ProgressV1)>,
     },
+    V2 {
+        import: FormatV1,
+        history: Vec<(BincodeDuration, ProgressV2)>,
+        events: Vec<EventV1>,
+        quality: Option<QualityV1>,
+        first_pass: Vec<(BincodeDuration, ProgressV2)>,
+        usage: Vec<(BincodeDuration, UsageV1)>,
+    },
 }
 
 impl From<&Stats> for Version {
     fn from(s: &Stats) -> Self {
-        Self::V1 {
+        Self::V2 {
             import: s.import.clone().into(),
             history: s
                 .history
@@ -35,6 +199,15 @@ impl From<&Stats> for Version {
                 .cloned()
                 .map(|(d, p)| (d.into(), p.into()))
                 .collect(),
+            events: s.events.iter().cloned().map(Into::into).collect(),
+            quality: s.quality.clone().map(Into::into),
+            first_pass: s
+                .first_pass
+                .iter()
+                .cloned()
+                .map(|(d, p)| (d.into(), p.into()))
+                .collect(),
+            usage: s.usage.iter().map(|&(d, u)| (d.into(), u.into())).collect(),
         }
     }
 }
@@ -48,6 +221,218 @@ impl From<Version> for Stats {
                     .into_iter()
                     .map(|(d, p)| (d.into(), p.into()))
                     .collect(),
+                events: Vec::new(),
+                quality: None,
+                first_pass: Vec::new(),
+                usage: Vec::new(),
+            },
+            Version::V2 {
+                import,
+                history,
+                events,
+                quality,
+                first_pass,
+                usage,
+            } => Stats {
+                import: import.into(),
+                history: history
+                    .into_iter()
+                    .map(|(d, p)| (d.into(), p.into()))
+                    .collect(),
+                events: events.into_iter().map(Into::into).collect(),
+                quality: quality.map(Into::into),
+                first_pass: first_pass
+                    .into_iter()
+                    .map(|(d, p)| (d.into(), p.into()))
+                    .collect(),
+                usage: usage
+                    .into_iter()
+                    .map(|(d, u)| (d.into(), u.into()))
+                    .collect(),
+            },
+        }
+    }
+}
+
+#[derive(Encode, Decode)]
+struct UsageV1 {
+    cpu_time: BincodeDuration,
+    rss: u64,
+    read_bytes: u64,
+    write_bytes: u64,
+}
+
+impl From<Usage> for UsageV1 {
+    fn from(u: Usage) -> Self {
+        Self {
+            cpu_time: u.cpu_time.into(),
+            rss: u.rss,
+            read_bytes: u.read_bytes,
+            write_bytes: u.write_bytes,
+        }
+    }
+}
+
+impl From<UsageV1> for Usage {
+    fn from(u: UsageV1) -> Self {
+        Self {
+            cpu_time: u.cpu_time.into(),
+            rss: u.rss,
+            read_bytes: u.read_bytes,
+            write_bytes: u.write_bytes,
+        }
+    }
+}
+
+#[derive(Encode, Decode)]
+struct QualityV1 {
+    ssim: Vec<f64>,
+    psnr: Vec<f64>,
+    vmaf: Vec<f64>,
+}
+
+impl From<Quality> for QualityV1 {
+    fn from(q: Quality) -> Self {
+        Self {
+            ssim: q.ssim,
+            psnr: q.psnr,
+            vmaf: q.vmaf,
+        }
+    }
+}
+
+impl From<QualityV1> for Quality {
+    fn from(q: QualityV1) -> Self {
+        Self {
+            ssim: q.ssim,
+            psnr: q.psnr,
+            vmaf: q.vmaf,
+        }
+    }
+}
+
+/// Encoded form of an [`Event`]. New variants must only ever be appended, so older files keep
+/// decoding correctly.
+#[derive(Encode, Decode)]
+enum EventV1 {
+    SizeGuard {
+        timestamp: BincodeDuration,
+        projected: u64,
+        limit: u64,
+        aborted: bool,
+    },
+    KeepSmaller {
+        input_size: u64,
+        output_size: u64,
+        kept_output: bool,
+        dry_run: bool,
+    },
+    Verified {
+        problems: Vec<String>,
+    },
+    Integrity {
+        errors: u64,
+        messages: Vec<String>,
+    },
+    CrfAttempt {
+        crf: u8,
+        vmaf: Option<f64>,
+        projected_size: u64,
+        passed: bool,
+    },
+    Warning {
+        timestamp: BincodeDuration,
+        message: String,
+    },
+}
+
+impl From<Event> for EventV1 {
+    fn from(e: Event) -> Self {
+        match e {
+            Event::SizeGuard {
+                timestamp,
+                projected,
+                limit,
+                aborted,
+            } => Self::SizeGuard {
+                timestamp: timestamp.into(),
+                projected,
+                limit,
+                aborted,
+            },
+            Event::KeepSmaller {
+                input_size,
+                output_size,
+                kept_output,
+                dry_run,
+            } => Self::KeepSmaller {
+                input_size,
+                output_size,
+                kept_output,
+                dry_run,
+            },
+            Event::Verified { problems } => Self::Verified { problems },
+            Event::Integrity { errors, messages } => Self::Integrity { errors, messages },
+            Event::CrfAttempt {
+                crf,
+                vmaf,
+                projected_size,
+                passed,
+            } => Self::CrfAttempt {
+                crf,
+                vmaf,
+                projected_size,
+                passed,
+            },
+            Event::Warning { timestamp, message } => Self::Warning {
+                timestamp: timestamp.into(),
+                message,
+            },
+        }
+    }
+}
+
+impl From<EventV1> for Event {
+    fn from(e: EventV1) -> Self {
+        match e {
+            EventV1::SizeGuard {
+                timestamp,
+                projected,
+                limit,
+                aborted,
+            } => Self::SizeGuard {
+                timestamp: timestamp.into(),
+                projected,
+                limit,
+                aborted,
+            },
+            EventV1::KeepSmaller {
+                input_size,
+                output_size,
+                kept_output,
+                dry_run,
+            } => Self::KeepSmaller {
+                input_size,
+                output_size,
+                kept_output,
+                dry_run,
+            },
+            EventV1::Verified { problems } => Self::Verified { problems },
+            EventV1::Integrity { errors, messages } => Self::Integrity { errors, messages },
+            EventV1::CrfAttempt {
+                crf,
+                vmaf,
+                projected_size,
+                passed,
+            } => Self::CrfAttempt {
+                crf,
+                vmaf,
+                projected_size,
+                passed,
+            },
+            EventV1::Warning { timestamp, message } => Self::Warning {
+                timestamp: timestamp.into(),
+                message,
             },
         }
     }
@@ -118,7 +503,40 @@ struct ProgressV1 {
     pub speed: f64,
 }
 
-impl From<Progress> for ProgressV1 {
+impl From<ProgressV1> for Progress {
+    fn from(p: ProgressV1) -> Self {
+        Self {
+            frame: p.frame,
+            fps: p.fps,
+            bitrate: Some(p.bitrate),
+            total_size: Some(p.total_size),
+            out_time_us: Some(p.out_time_us as i64),
+            out_time_ms: Some(p.out_time_ms as i64),
+            out_time: Some(p.out_time.into()),
+            dup_frames: p.dup_frames,
+            drop_frames: p.drop_frames,
+            speed: Some(p.speed),
+            quantizers: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Encode, Decode)]
+struct ProgressV2 {
+    pub frame: u64,
+    pub fps: f64,
+    pub bitrate: Option<u64>,
+    pub total_size: Option<u64>,
+    pub out_time_us: Option<i64>,
+    pub out_time_ms: Option<i64>,
+    pub out_time: Option<BincodeDuration>,
+    pub dup_frames: u64,
+    pub drop_frames: u64,
+    pub speed: Option<f64>,
+    pub quantizers: BTreeMap<(u32, u32), f64>,
+}
+
+impl From<Progress> for ProgressV2 {
     fn from(p: Progress) -> Self {
         Self {
             frame: p.frame,
@@ -127,16 +545,17 @@ impl From<Progress> for ProgressV1 {
             total_size: p.total_size,
             out_time_us: p.out_time_us,
             out_time_ms: p.out_time_ms,
-            out_time: p.out_time.into(),
+            out_time: p.out_time.map(Into::into),
             dup_frames: p.dup_frames,
             drop_frames: p.drop_frames,
             speed: p.speed,
+            quantizers: p.quantizers,
         }
     }
 }
 
-impl From<ProgressV1> for Progress {
-    fn from(p: ProgressV1) -> Self {
+impl From<ProgressV2> for Progress {
+    fn from(p: ProgressV2) -> Self {
         Self {
             frame: p.frame,
             fps: p.fps,
@@ -144,22 +563,27 @@ impl From<ProgressV1> for Progress {
             total_size: p.total_size,
             out_time_us: p.out_time_us,
             out_time_ms: p.out_time_ms,
-            out_time: p.out_time.into(),
+            out_time: p.out_time.map(Into::into),
             dup_frames: p.dup_frames,
             drop_frames: p.drop_frames,
             speed: p.speed,
+            quantizers: p.quantizers,
         }
     }
 }
 
-pub fn save(stats: &Stats, input: &Path) -> Result<()> {
-    let input = {
-        let mut os_str = input.as_os_str().to_os_string();
-        os_str.push(".stats");
-        PathBuf::from(os_str)
-    };
+/// Location of the statistics file that belongs to the given input file.
+pub fn path(input: &Path) -> PathBuf {
+    let mut os_str = input.as_os_str().to_os_string();
+    os_str.push(".stats");
+    PathBuf::from(os_str)
+}
 
-    let mut dst = GzEncoder::new(BufWriter::new(File::create(input)?), Compression::best());
+pub fn save(stats: &Stats, input: &Path) -> Result<()> {
+    let mut dst = GzEncoder::new(
+        BufWriter::new(File::create(path(input))?),
+        Compression::best(),
+    );
     let version = Version::from(stats);
 
     bincode::encode_into_std_write(version, &mut dst, config::standard())?;
@@ -170,13 +594,7 @@ pub fn save(stats: &Stats, input: &Path) -> Result<()> {
 }
 
 pub fn load(input: &Path) -> Result<Stats> {
-    let input = {
-        let mut os_str = input.as_os_str().to_os_string();
-        os_str.push(".stats");
-        PathBuf::from(os_str)
-    };
-
-    let mut src = GzDecoder::new(BufReader::new(File::open(input)?));
+    let mut src = GzDecoder::new(BufReader::new(File::open(path(input))?));
     let version = bincode::decode_from_std_read::<Version, _, _>(&mut src, config::standard())?;
 
     Ok(version.into())
@@ -227,3 +645,69 @@ impl From<BincodeDuration> for Duration {
         d.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use time::Duration;
+
+    use super::{Event, Stats};
+    use crate::{ffmpeg::Progress, ffprobe::Format, usage::Usage};
+
+    #[test]
+    fn save_and_load() {
+        let dir = std::env::temp_dir().join(format!("ffprog-stats-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("in.mkv");
+
+        let progress = Progress {
+            out_time: Some(Duration::seconds(2)),
+            total_size: None,
+            ..Progress::default()
+        };
+        let stats = Stats {
+            import: Format {
+                duration: Duration::seconds(10),
+                ..Format::default()
+            },
+            history: vec![(Duration::SECOND, progress.clone())],
+            events: vec![Event::integrity(vec!["error".to_owned()])],
+            quality: None,
+            first_pass: vec![(Duration::SECOND, progress)],
+            usage: vec![(
+                Duration::SECOND,
+                Usage {
+                    cpu_time: Duration::SECOND,
+                    rss: 1024,
+                    read_bytes: 10,
+                    write_bytes: 20,
+                },
+            )],
+        };
+
+        super::save(&stats, &input).unwrap();
+        let loaded = super::load(&input).unwrap();
+        fs::remove_dir_all(dir).ok();
+
+        assert_eq!(Duration::seconds(10), loaded.import.duration);
+        assert_eq!(Some(Duration::seconds(2)), loaded.history[0].1.out_time);
+        assert_eq!(None, loaded.first_pass[0].1.total_size);
+        assert!(loaded.failure().is_some());
+        assert_eq!(20, loaded.usage[0].1.write_bytes);
+    }
+
+    #[test]
+    fn integrity_event() {
+        let log = (0..30).map(|i| format!("error {i}")).chain([String::new()]);
+
+        match Event::integrity(log.collect()) {
+            event @ Event::Integrity { errors, .. } => {
+                assert_eq!(30, errors);
+                assert!(event.is_failure());
+            }
+            _ => panic!("expected an integrity event"),
+        }
+        assert!(!Event::integrity(vec![" ".to_owned()]).is_failure());
+    }
+}
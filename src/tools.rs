@@ -0,0 +1,186 @@
+use std::{
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::OnceLock,
+};
+
+use anyhow::{ensure, Context, Result};
+
+/// Oldest FFmpeg version that supports all the options, that are passed to it. Mostly
+/// `-stats_period`, which was added in version 4.4.
+const MIN_VERSION: Version = Version { major: 4, minor: 4 };
+
+/// Paths of the FFmpeg binaries, that are set once at startup.
+static TOOLS: OnceLock<Tools> = OnceLock::new();
+
+/// Locations of the `ffmpeg` and `ffprobe` binaries. Plain names are searched for in the `PATH`.
+pub struct Tools {
+    pub ffmpeg: PathBuf,
+    pub ffprobe: PathBuf,
+}
+
+impl Default for Tools {
+    fn default() -> Self {
+        Self {
+            ffmpeg: "ffmpeg".into(),
+            ffprobe: "ffprobe".into(),
+        }
+    }
+}
+
+/// Use the given binaries for all following FFmpeg calls, after checking that they can be run and
+/// are recent enough.
+pub fn init(tools: Tools) -> Result<()> {
+    match check(&tools.ffmpeg, "ffmpeg")? {
+        Some(version) => ensure!(
+            version >= MIN_VERSION,
+            "FFmpeg {version} at `{}` is too old, at least version {MIN_VERSION} is required \
+             for the `-stats_period` option",
+            tools.ffmpeg.display()
+        ),
+        None => ensure!(
+            supports_stats_period(&tools.ffmpeg)?,
+            "FFmpeg at `{}` doesn't support the `-stats_period` option, at least version \
+             {MIN_VERSION} is required",
+            tools.ffmpeg.display()
+        ),
+    }
+
+    check(&tools.ffprobe, "ffprobe")?;
+
+    TOOLS.set(tools).ok();
+    Ok(())
+}
+
+/// Create a command for running `ffmpeg`.
+pub fn ffmpeg() -> Command {
+    Command::new(TOOLS.get_or_init(Tools::default).ffmpeg.as_os_str())
+}
+
+/// Create a command for running `ffprobe`.
+pub fn ffprobe() -> Command {
+    Command::new(TOOLS.get_or_init(Tools::default).ffprobe.as_os_str())
+}
+
+/// Run the binary with `-version`, and parse the version from its first line.
+fn check(path: &Path, name: &str) -> Result<Option<Version>> {
+    let output = Command::new(path)
+        .arg("-version")
+        .output()
+        .with_context(|| format!("failed running {name} at `{}`", path.display()))?;
+
+    ensure!(
+        output.status.success(),
+        "{name} at `{}` failed reporting its version:\n{}",
+        path.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .and_then(|line| parse_version(line, name)))
+}
+
+/// Check the full help of `ffmpeg` for the `-stats_period` option, for builds whose version
+/// can't be parsed.
+fn supports_stats_period(path: &Path) -> Result<bool> {
+    let output = Command::new(path)
+        .args(["-hide_banner", "-h", "full"])
+        .output()
+        .with_context(|| format!("failed running ffmpeg at `{}`", path.display()))?;
+
+    Ok(has_stats_period(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Whether the help output lists the `-stats_period` option.
+fn has_stats_period(help: &str) -> bool {
+    help.lines()
+        .any(|line| line.trim_start().starts_with("-stats_period "))
+}
+
+/// FFmpeg release version, without the patch level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Version {
+    major: u32,
+    minor: u32,
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Parse the version from the first line of `-version`, like `ffmpeg version 6.0 Copyright ...`.
+/// Builds from the development branch (`N-109421-g…`) have no release version, and result in
+/// `None`, as do unknown formats.
+fn parse_version(line: &str, name: &str) -> Option<Version> {
+    let version = line
+        .strip_prefix(name)?
+        .trim_start()
+        .strip_prefix("version")?
+        .split_whitespace()
+        .next()?;
+    // Distributions prefix the version with `n` or append their own revision, like
+    // `4.4.2-0ubuntu0.22.04.1`.
+    let version = version.strip_prefix('n').unwrap_or(version);
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+
+    Some(Version {
+        major: parts.next()?.parse().ok()?,
+        minor: parts
+            .next()
+            .and_then(|minor| minor.parse().ok())
+            .unwrap_or(0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{has_stats_period, parse_version, Version};
+
+    #[test]
+    fn parse_versions() {
+        for (line, expect) in [
+            (
+                "ffmpeg version 6.0 Copyright (c) 2000-2023 the FFmpeg developers",
+                Some((6, 0)),
+            ),
+            (
+                "ffmpeg version 4.4.2-0ubuntu0.22.04.1 Copyright (c) 2000-2021",
+                Some((4, 4)),
+            ),
+            (
+                "ffmpeg version n5.1.2 Copyright (c) 2000-2022",
+                Some((5, 1)),
+            ),
+            ("ffmpeg version 7 Copyright (c) 2000-2024", Some((7, 0))),
+            ("ffmpeg version N-109421-g9adf02247c-static", None),
+            ("ffprobe version 6.0 Copyright (c) 2007-2023", None),
+            ("something else entirely", None),
+        ] {
+            assert_eq!(
+                expect.map(|(major, minor)| Version { major, minor }),
+                parse_version(line, "ffmpeg"),
+                "{line}"
+            );
+        }
+
+        assert!(Version { major: 4, minor: 3 } < super::MIN_VERSION);
+        assert!(Version { major: 5, minor: 0 } > super::MIN_VERSION);
+    }
+
+    #[test]
+    fn find_stats_period() {
+        assert!(has_stats_period(
+            "-stats             print progress report during encoding\n\
+             -stats_period time  set the period at which ffmpeg updates stats and -progress output"
+        ));
+        assert!(!has_stats_period(
+            "-stats             print progress report during encoding\n\
+             -progress url       write program-readable progress information"
+        ));
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    fmt::{self, Display},
+    fs,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use anyhow::{ensure, Result};
+use time::Duration;
+
+use crate::{
+    ffprobe::{self, Format},
+    tools,
+};
+
+/// Short segment of the input, that is encoded instead of the full file.
+#[derive(Clone, Copy)]
+pub struct Sample {
+    pub start: Duration,
+    pub length: Duration,
+}
+
+/// Name of a single sample encode in the queue of a trial, in place of an input file.
+pub struct SampleLabel {
+    /// Name of the argument set, that the sample was encoded with.
+    pub set: String,
+    /// Position of the sample, counting from 1.
+    pub sample: usize,
+}
+
+impl Display for SampleLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, sample {}", self.set, self.sample)
+    }
+}
+
+/// Spread the given amount of samples evenly across the input, each centered in its own part of
+/// the file. If the input is too short, a single sample covers all of it.
+pub fn samples(duration: Duration, count: usize, length: Duration) -> Vec<Sample> {
+    if count == 0 || duration <= length * count as u32 {
+        return vec![Sample {
+            start: Duration::ZERO,
+            length: duration,
+        }];
+    }
+
+    let part = duration / count as u32;
+
+    (0..count)
+        .map(|i| Sample {
+            start: part * i as u32 + (part - length) / 2,
+            length,
+        })
+        .collect()
+}
+
+/// Copy a sample out of the input without re-encoding it. The cut snaps to the nearest keyframes,
+/// so the actual length of the sample may differ slightly.
+fn extract(input: &Path, sample: Sample, output: &Path) -> Result<()> {
+    let result = tools::ffmpeg()
+        .args(["-nostdin", "-hide_banner", "-loglevel", "error", "-y"])
+        .args(["-ss", &sample.start.as_seconds_f64().to_string()])
+        .arg("-i")
+        .arg(input)
+        .args(["-t", &sample.length.as_seconds_f64().to_string()])
+        .args(["-map", "0", "-c", "copy"])
+        .arg(output)
+        .stdin(Stdio::null())
+        .output()?;
+
+    ensure!(
+        result.status.success(),
+        "failed extracting a sample: {}",
+        String::from_utf8_lossy(&result.stderr).trim()
+    );
+
+    Ok(())
+}
+
+/// Extract evenly spread samples of the input into the directory, returning the probed format of
+/// each of them.
+pub fn extract_samples(
+    input: &Path,
+    format: &Format,
+    count: usize,
+    length: Duration,
+    dir: &Path,
+    extension: &str,
+) -> Result<Vec<Format>> {
+    samples(format.duration, count, length)
+        .into_iter()
+        .enumerate()
+        .map(|(i, sample)| {
+            let path = dir.join(format!("sample-{i}.{extension}"));
+            extract(input, sample, &path)?;
+            ffprobe::run(&path)
+        })
+        .collect()
+}
+
+/// Totals over all sample encodes of one set of arguments, to project the result for the full
+/// file.
+#[derive(Default)]
+pub struct Estimate {
+    /// Combined media duration of all samples.
+    pub duration: Duration,
+    pub output_size: u64,
+    /// Time it took to encode all samples.
+    pub encode_time: Duration,
+}
+
+impl Estimate {
+    pub fn add(&mut self, duration: Duration, output_size: u64, time: Duration) {
+        self.duration += duration;
+        self.output_size += output_size;
+        self.encode_time += time;
+    }
+
+    /// Projected size of the full output, scaled up from the samples by duration.
+    pub fn projected_size(&self, full_duration: Duration) -> u64 {
+        if self.duration.is_zero() {
+            return 0;
+        }
+
+        (self.output_size as f64 * full_duration.as_seconds_f64() / self.duration.as_seconds_f64())
+            as u64
+    }
+
+    /// Average bitrate of the encoded samples, in bits per second.
+    pub fn bitrate(&self) -> u64 {
+        if self.duration.is_zero() {
+            return 0;
+        }
+
+        (self.output_size as f64 * 8.0 / self.duration.as_seconds_f64()) as u64
+    }
+
+    /// Encoding speed, relative to real-time playback.
+    pub fn speed(&self) -> f64 {
+        if self.encode_time.is_zero() {
+            return 0.0;
+        }
+
+        self.duration.as_seconds_f64() / self.encode_time.as_seconds_f64()
+    }
+}
+
+/// Temporary directory for intermediate files, like samples and their encodes, removed once
+/// dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create a directory for this process, with the name telling what it's used for.
+    pub fn new(name: &str) -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!(
+            "{}-{name}-{}",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self(dir))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Duration;
+
+    use super::Estimate;
+
+    #[test]
+    fn spread_samples() {
+        let samples = super::samples(Duration::seconds(100), 4, Duration::seconds(10));
+        let starts = samples
+            .iter()
+            .map(|s| s.start.whole_seconds())
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![7, 32, 57, 82], starts);
+
+        let samples = super::samples(Duration::seconds(30), 4, Duration::seconds(10));
+        assert_eq!(1, samples.len());
+        assert_eq!(Duration::seconds(30), samples[0].length);
+    }
+
+    #[test]
+    fn project_from_samples() {
+        let mut estimate = Estimate::default();
+        estimate.add(Duration::seconds(10), 1000, Duration::seconds(5));
+        estimate.add(Duration::seconds(10), 1500, Duration::seconds(5));
+
+        assert_eq!(12_500, estimate.projected_size(Duration::seconds(100)));
+        assert_eq!(1000, estimate.bitrate());
+        assert!((estimate.speed() - 2.0).abs() < f64::EPSILON);
+    }
+}
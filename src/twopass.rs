@@ -0,0 +1,136 @@
+use std::{cmp::Ordering, path::Path};
+
+use anyhow::{Context, Result};
+use time::Duration;
+use tui::{
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Paragraph},
+};
+
+use crate::{ffmpeg, trial::TempDir, Header, Phase};
+
+/// Names of both passes, as shown in the progress view.
+const PASSES: [&str; 2] = ["analysis", "encoding"];
+
+/// Two-pass encoding, where the first pass only analyzes the input and writes its findings into a
+/// log file, that the second pass uses to distribute the bitrate.
+pub struct TwoPass {
+    dir: TempDir,
+    /// Index of the currently running pass.
+    pass: usize,
+}
+
+impl TwoPass {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            dir: TempDir::new("two-pass")?,
+            pass: 0,
+        })
+    }
+
+    /// Create the FFmpeg arguments for the current pass.
+    pub fn args(&self, args: &[String]) -> Result<Vec<String>> {
+        pass_args(args, self.pass + 1, &self.dir.path().join("passlog"))
+    }
+
+    /// Move on to the second pass.
+    pub fn next(&mut self) {
+        self.pass = (self.pass + 1).min(PASSES.len() - 1);
+    }
+}
+
+impl Header for TwoPass {
+    fn create(&self, _in_progress: Duration, title: &str) -> Paragraph<'_> {
+        let label = Style::default().fg(Color::Blue);
+        let spans = PASSES
+            .iter()
+            .enumerate()
+            .flat_map(|(i, name)| {
+                let style = match i.cmp(&self.pass) {
+                    Ordering::Less => Style::default().fg(Color::Green),
+                    Ordering::Equal => Style::default().fg(Color::White),
+                    Ordering::Greater => Style::default().fg(Color::DarkGray),
+                };
+
+                [
+                    Span::raw(if i == 0 { "" } else { "  " }),
+                    Span::styled(format!("Pass {}: {name}", i + 1), style),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        Paragraph::new(Spans::from(spans)).block(
+            Block::default()
+                .title(Span::styled(format!("Two-pass / {title}"), label))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded),
+        )
+    }
+
+    fn phase(&self) -> Option<Phase> {
+        Some(Phase {
+            index: self.pass,
+            count: PASSES.len(),
+            name: PASSES[self.pass],
+        })
+    }
+}
+
+/// Inject the options of the given pass into the arguments, right before the output file. The
+/// first pass discards its output, as only the log file is needed from it.
+fn pass_args(args: &[String], pass: usize, log: &Path) -> Result<Vec<String>> {
+    let (output, rest) = args
+        .split_last()
+        .filter(|_| ffmpeg::output_path(args).is_some())
+        .context("two-pass encoding requires the output file as the last argument")?;
+    let log = log.to_str().context("pass log path is not valid UTF-8")?;
+
+    let mut args = rest.to_vec();
+    args.extend(["-pass".to_owned(), pass.to_string()]);
+    args.extend(["-passlogfile".to_owned(), log.to_owned()]);
+
+    if pass == 1 {
+        args.extend(["-an", "-f", "null", "-"].map(ToOwned::to_owned));
+    } else {
+        args.push(output.clone());
+    }
+
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn inject_pass_args() {
+        let args = ["-i", "in.mkv", "-c:v", "libx264", "-b:v", "2M", "out.mp4"].map(String::from);
+
+        assert_eq!(
+            [
+                "-i",
+                "in.mkv",
+                "-c:v",
+                "libx264",
+                "-b:v",
+                "2M",
+                "-pass",
+                "1",
+                "-passlogfile",
+                "/tmp/log",
+                "-an",
+                "-f",
+                "null",
+                "-"
+            ]
+            .as_slice(),
+            super::pass_args(&args, 1, "/tmp/log".as_ref())
+                .unwrap()
+                .as_slice()
+        );
+        assert_eq!(
+            ["-pass", "2", "-passlogfile", "/tmp/log", "out.mp4"].as_slice(),
+            &super::pass_args(&args, 2, "/tmp/log".as_ref()).unwrap()[6..]
+        );
+        assert!(super::pass_args(&args[..5], 1, "/tmp/log".as_ref()).is_err());
+    }
+}
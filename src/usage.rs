@@ -0,0 +1,190 @@
+use std::{fs, path::Path};
+
+use time::Duration;
+
+/// Resource usage of a process, as cumulative counters since its start. Read from `/proc`, so only
+/// available on Linux.
+#[derive(Clone, Copy, Default)]
+pub struct Usage {
+    /// CPU time spent in user and kernel mode, over all threads.
+    pub cpu_time: Duration,
+    /// Resident memory, in bytes.
+    pub rss: u64,
+    /// Bytes read from storage.
+    pub read_bytes: u64,
+    /// Bytes written to storage.
+    pub write_bytes: u64,
+}
+
+impl Usage {
+    /// Sample the current usage of the given process. The I/O counters are left at zero, if they
+    /// can't be read.
+    #[cfg(target_os = "linux")]
+    pub fn sample(pid: u32) -> Option<Self> {
+        let read = |name| fs::read_to_string(format!("/proc/{pid}/{name}")).ok();
+        let (read_bytes, write_bytes) = read("io").and_then(|io| parse_io(&io)).unwrap_or((0, 0));
+
+        Some(Self {
+            cpu_time: parse_cpu_time(&read("stat")?, ticks_per_second())?,
+            rss: parse_rss(&read("status")?)?,
+            read_bytes,
+            write_bytes,
+        })
+    }
+
+    /// Resource usage can only be sampled on Linux.
+    #[cfg(not(target_os = "linux"))]
+    pub fn sample(_pid: u32) -> Option<Self> {
+        None
+    }
+
+    /// Average rates between an earlier sample and this one.
+    pub fn rates(&self, earlier: &Self, elapsed: Duration) -> Rates {
+        let seconds = elapsed.as_seconds_f64();
+        if seconds <= 0.0 {
+            return Rates::default();
+        }
+
+        Rates {
+            cpu: (self.cpu_time - earlier.cpu_time).as_seconds_f64() / seconds * 100.0,
+            read: self.read_bytes.saturating_sub(earlier.read_bytes) as f64 / seconds,
+            write: self.write_bytes.saturating_sub(earlier.write_bytes) as f64 / seconds,
+        }
+    }
+}
+
+/// Usage of a process over a time span.
+#[derive(Default)]
+pub struct Rates {
+    /// CPU load in percent, where 100% is a single fully used core.
+    pub cpu: f64,
+    /// Bytes read per second.
+    pub read: f64,
+    /// Bytes written per second.
+    pub write: f64,
+}
+
+/// Current read position of the process in the given file, taken from the file descriptor that
+/// has it open.
+pub fn input_position(pid: u32, path: &Path) -> Option<u64> {
+    let path = path.canonicalize().ok()?;
+
+    fs::read_dir(format!("/proc/{pid}/fd"))
+        .ok()?
+        .filter_map(Result::ok)
+        .find(|fd| fs::read_link(fd.path()).is_ok_and(|target| target == path))
+        .and_then(|fd| {
+            let fd = fd.file_name();
+            let fdinfo = fs::read_to_string(format!("/proc/{pid}/fdinfo/{}", fd.to_str()?));
+            parse_position(&fdinfo.ok()?)
+        })
+}
+
+/// Parse the file position from `/proc/<pid>/fdinfo/<fd>`.
+fn parse_position(fdinfo: &str) -> Option<u64> {
+    fdinfo
+        .lines()
+        .find_map(|line| line.strip_prefix("pos:")?.trim().parse().ok())
+}
+
+/// Clock ticks per second, that CPU times in `/proc` are counted in. Falls back to the usual 100,
+/// if the system doesn't report it.
+#[cfg(target_os = "linux")]
+fn ticks_per_second() -> i64 {
+    // SAFETY: Plain system call without any pointers.
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    u16::try_from(ticks)
+        .ok()
+        .filter(|&ticks| ticks > 0)
+        .map_or(100, i64::from)
+}
+
+/// Parse the user and system time from `/proc/<pid>/stat`, counted in clock ticks. They're the
+/// 14th and 15th field, but as the process name in the 2nd field may contain spaces, counting
+/// starts after it.
+#[cfg(target_os = "linux")]
+fn parse_cpu_time(stat: &str, ticks_per_second: i64) -> Option<Duration> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    let mut fields = rest.split_whitespace().skip(11);
+    let utime = fields.next()?.parse::<i64>().ok()?;
+    let stime = fields.next()?.parse::<i64>().ok()?;
+
+    Some(Duration::milliseconds(
+        (utime + stime) * 1000 / ticks_per_second,
+    ))
+}
+
+/// Parse the resident memory from `/proc/<pid>/status`, given in KiB.
+#[cfg(target_os = "linux")]
+fn parse_rss(status: &str) -> Option<u64> {
+    status.lines().find_map(|line| {
+        let value = line.strip_prefix("VmRSS:")?.trim().strip_suffix("kB")?;
+        Some(value.trim().parse::<u64>().ok()? * 1024)
+    })
+}
+
+/// Parse the bytes read from and written to storage from `/proc/<pid>/io`.
+#[cfg(target_os = "linux")]
+fn parse_io(io: &str) -> Option<(u64, u64)> {
+    let field = |name: &str| {
+        io.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(": ")?.parse().ok())
+    };
+
+    Some((field("read_bytes")?, field("write_bytes")?))
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Duration;
+
+    use super::Usage;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn parse_proc_files() {
+        let stat = "4242 (ffmpeg (x) 1) S 1 4242 4242 0 -1 4194560 5243 0 0 0 1234 56 0 0 20 0 17 \
+                    0 104553 2451554304 20000 18446744073709551615";
+        let status = "Name:\tffmpeg\nVmPeak:\t  250000 kB\nVmRSS:\t  123456 kB\nThreads:\t17\n";
+        let io = "rchar: 9000\nwchar: 8000\nsyscr: 10\nsyscw: 20\nread_bytes: 4096\n\
+                  write_bytes: 8192\ncancelled_write_bytes: 0\n";
+
+        assert_eq!(
+            Some(Duration::milliseconds(12_900)),
+            super::parse_cpu_time(stat, 100)
+        );
+        assert_eq!(
+            Some(Duration::milliseconds(1_290)),
+            super::parse_cpu_time(stat, 1000)
+        );
+        assert_eq!(Some(123_456 * 1024), super::parse_rss(status));
+        assert_eq!(Some((4096, 8192)), super::parse_io(io));
+        assert_eq!(None, super::parse_rss("Name:\tkthreadd\n"));
+        assert_eq!(
+            Some(1_048_576),
+            super::parse_position("pos:\t1048576\nflags:\t0100000\nmnt_id:\t29\n")
+        );
+    }
+
+    #[test]
+    fn usage_rates() {
+        let earlier = Usage {
+            cpu_time: Duration::seconds(10),
+            rss: 0,
+            read_bytes: 1000,
+            write_bytes: 0,
+        };
+        let later = Usage {
+            cpu_time: Duration::seconds(16),
+            rss: 0,
+            read_bytes: 5000,
+            write_bytes: 2000,
+        };
+
+        let rates = later.rates(&earlier, Duration::seconds(2));
+        assert_eq!(300.0, rates.cpu);
+        assert_eq!(2000.0, rates.read);
+        assert_eq!(1000.0, rates.write);
+        assert_eq!(0.0, later.rates(&earlier, Duration::ZERO).cpu);
+    }
+}
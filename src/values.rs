@@ -1,15 +1,135 @@
+use std::{collections::VecDeque, fmt, str::FromStr};
+
+use anyhow::{bail, Context, Error, Result};
 use tui::{
+    buffer::Buffer,
     layout::Rect,
     style::{Color, Style},
     symbols::Marker,
     text::Span,
-    widgets::{Axis, Block, BorderType, Borders, Chart, Dataset, GraphType, Sparkline},
+    widgets::{Axis, Block, BorderType, Borders, Chart, Dataset, GraphType, Sparkline, Widget},
 };
 
-use crate::array::Array;
+use crate::array::{Array, MinMax};
+
+/// Smoothing method, applied to noisy values to make trends visible.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Smoothing {
+    #[default]
+    Off,
+    /// Simple moving average over the given amount of samples.
+    Sma(usize),
+    /// Exponential moving average with a span of the given amount of samples.
+    Ema(usize),
+}
+
+impl Smoothing {
+    /// The windows that can be cycled through in the UI.
+    const PRESETS: [Self; 5] = [
+        Self::Off,
+        Self::Sma(5),
+        Self::Sma(20),
+        Self::Ema(5),
+        Self::Ema(20),
+    ];
+
+    /// Switch to the next preset. Any custom window is followed by the first preset.
+    pub fn next(self) -> Self {
+        let pos = Self::PRESETS.iter().position(|s| *s == self);
+        match pos {
+            Some(pos) => Self::PRESETS[(pos + 1) % Self::PRESETS.len()],
+            None => Self::PRESETS[0],
+        }
+    }
+
+    pub fn apply(self, values: impl IntoIterator<Item = f64>) -> Vec<f64> {
+        let mut smoother = Smoother::new(self);
+        values.into_iter().map(|v| smoother.push(v)).collect()
+    }
+}
+
+/// Running state of a [`Smoothing`], that smooths each new value in O(1) instead of going over
+/// the whole series again.
+struct Smoother {
+    smoothing: Smoothing,
+    /// Values in the current window of a simple moving average.
+    window: VecDeque<f64>,
+    sum: f64,
+    /// Last value of an exponential moving average.
+    average: Option<f64>,
+}
+
+impl Smoother {
+    fn new(smoothing: Smoothing) -> Self {
+        Self {
+            smoothing,
+            window: VecDeque::new(),
+            sum: 0.0,
+            average: None,
+        }
+    }
+
+    /// Add the next value and return its smoothed counterpart.
+    fn push(&mut self, value: f64) -> f64 {
+        match self.smoothing {
+            Smoothing::Off | Smoothing::Sma(0) | Smoothing::Ema(0) => value,
+            Smoothing::Sma(window) => {
+                self.window.push_back(value);
+                self.sum += value;
+                if self.window.len() > window {
+                    self.sum -= self.window.pop_front().unwrap_or_default();
+                }
+                self.sum / self.window.len() as f64
+            }
+            Smoothing::Ema(window) => {
+                let alpha = 2.0 / (window as f64 + 1.0);
+                let next = match self.average {
+                    Some(avg) => alpha * value + (1.0 - alpha) * avg,
+                    None => value,
+                };
+                self.average = Some(next);
+                next
+            }
+        }
+    }
+}
+
+impl fmt::Display for Smoothing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Off => f.write_str("off"),
+            Self::Sma(window) => write!(f, "sma:{window}"),
+            Self::Ema(window) => write!(f, "ema:{window}"),
+        }
+    }
+}
+
+impl FromStr for Smoothing {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "off" {
+            return Ok(Self::Off);
+        }
+
+        let (kind, window) = s
+            .split_once(':')
+            .context("expected `off`, `sma:<window>` or `ema:<window>`")?;
+        let window = window.parse().context("invalid window size")?;
+
+        Ok(match kind {
+            "sma" => Self::Sma(window),
+            "ema" => Self::Ema(window),
+            _ => bail!("unknown smoothing method `{kind}`"),
+        })
+    }
+}
 
 pub struct SparklineValues<F> {
-    history: Array<u64, 500>,
+    history: Array<u64>,
+    smoothing: Smoothing,
+    smoother: Smoother,
+    smoothed: Array<f64>,
     max: u64,
     current: f64,
     labeler: F,
@@ -19,16 +139,19 @@ impl<F> SparklineValues<F>
 where
     F: Fn(f64) -> String,
 {
-    pub fn new(labeler: F) -> Self {
+    pub fn new(capacity: usize, labeler: F) -> Self {
         Self {
-            history: Array::new(0),
+            history: Array::with_capacity(capacity),
+            smoothing: Smoothing::Off,
+            smoother: Smoother::new(Smoothing::Off),
+            smoothed: Array::with_capacity(capacity),
             max: 0,
             current: 0.0,
             labeler,
         }
     }
 
-    pub fn create(&self, area: Rect) -> Sparkline {
+    pub fn create(&self, area: Rect) -> Sparkline<'_> {
         let data = self.history.as_slice();
         let data = &data[data
             .len()
@@ -38,7 +161,12 @@ where
             .block(
                 Block::default()
                     .title(Span::styled(
-                        (self.labeler)(self.current),
+                        title(
+                            &self.labeler,
+                            self.current,
+                            self.smoothing,
+                            Some(self.smoothed.last() / 100.0),
+                        ),
                         Style::default().fg(Color::Blue),
                     ))
                     .borders(Borders::ALL)
@@ -49,17 +177,85 @@ where
             .max(self.max)
     }
 
+    /// Create the line of smoothed values, to be rendered on top of the sparkline in the same
+    /// area. Nothing is returned if smoothing is disabled.
+    pub fn overlay(&self, area: Rect) -> Option<Overlay<'_>> {
+        if self.smoothing == Smoothing::Off {
+            return None;
+        }
+
+        let data = self.smoothed.as_slice();
+        let data = &data[data
+            .len()
+            .saturating_sub(area.width.saturating_sub(2) as usize)..];
+
+        Some(Overlay {
+            data,
+            max: self.max,
+            style: Style::default().fg(Color::Red),
+        })
+    }
+
+    /// Switch the smoothing, which smooths the current history once again.
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.smoothing = smoothing;
+        self.smoother = Smoother::new(smoothing);
+        self.smoothed.clear();
+
+        for i in 0..self.history.as_slice().len() {
+            self.smooth(self.history.as_slice()[i]);
+        }
+    }
+
     pub fn update(&mut self, value: f64) {
         self.current = value;
 
         let value = (value * 100.0).round() as u64;
         self.history.push(value);
         self.max = self.max.max(value);
+        self.smooth(value);
+    }
+
+    fn smooth(&mut self, value: u64) {
+        if self.smoothing != Smoothing::Off {
+            self.smoothed.push(self.smoother.push(value as f64));
+        }
+    }
+}
+
+/// Line of values, drawn over a [`Sparkline`] with the same scale. Each column gets a single dot
+/// at the height of its value.
+pub struct Overlay<'a> {
+    data: &'a [f64],
+    max: u64,
+    style: Style,
+}
+
+impl<'a> Widget for Overlay<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let area = Block::default().borders(Borders::ALL).inner(area);
+        if area.height < 1 || self.max == 0 {
+            return;
+        }
+
+        for (i, value) in self.data.iter().take(area.width as usize).enumerate() {
+            let row = (value / self.max as f64 * f64::from(area.height))
+                .floor()
+                .clamp(0.0, f64::from(area.height - 1)) as u16;
+
+            buf.get_mut(area.left() + i as u16, area.bottom() - 1 - row)
+                .set_symbol("•")
+                .set_style(self.style);
+        }
     }
 }
 
 pub struct ChartValues<F> {
-    history: Array<(f64, f64), 1000>,
+    history: Array<(f64, f64)>,
+    extrema: MinMax<f64>,
+    smoothing: Smoothing,
+    smoother: Smoother,
+    smoothed: Array<(f64, f64)>,
     baseline: [(f64, f64); 2],
     current: f64,
     min: f64,
@@ -71,9 +267,13 @@ impl<F> ChartValues<F>
 where
     F: Fn(f64) -> String,
 {
-    pub fn new(baseline: f64, labeler: F) -> Self {
+    pub fn new(capacity: usize, baseline: f64, labeler: F) -> Self {
         Self {
-            history: Array::default(),
+            history: Array::with_capacity(capacity),
+            extrema: MinMax::new(capacity),
+            smoothing: Smoothing::Off,
+            smoother: Smoother::new(Smoothing::Off),
+            smoothed: Array::with_capacity(capacity),
             baseline: [(0.0, baseline); 2],
             current: 0.0,
             min: 0.0,
@@ -93,15 +293,25 @@ where
             .graph_type(GraphType::Line)
             .style(Style::default().fg(Color::Green))
             .data(self.history.as_slice());
+        let smoothed = Dataset::default()
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Yellow))
+            .data(self.smoothed.as_slice());
 
         let y_min = self.min.min(self.baseline[0].1 * 0.9).max(0.0);
         let y_max = self.max.max(self.baseline[0].1 * 1.1);
 
-        Chart::new(vec![baseline, history])
+        Chart::new(vec![baseline, history, smoothed])
             .block(
                 Block::default()
                     .title(Span::styled(
-                        (self.labeler)(self.current),
+                        title(
+                            &self.labeler,
+                            self.current,
+                            self.smoothing,
+                            Some(self.smoothed.last().1),
+                        ),
                         Style::default().fg(Color::Blue),
                     ))
                     .borders(Borders::ALL)
@@ -128,12 +338,93 @@ where
         self.baseline[0].0 = self.history.first().0;
         self.baseline[1].0 = self.history.last().0;
 
-        self.min = f64::MAX;
-        self.max = 0.0f64;
+        self.extrema.push(value);
+        self.min = self.extrema.min().unwrap_or(f64::MAX);
+        self.max = self.extrema.max().unwrap_or_default().max(0.0);
+
+        self.smooth(self.history.last());
+    }
+
+    /// Switch the smoothing, which smooths the current history once again.
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.smoothing = smoothing;
+        self.smoother = Smoother::new(smoothing);
+        self.smoothed.clear();
 
-        for (_, v) in self.history.as_slice().iter().copied() {
-            self.min = self.min.min(v);
-            self.max = self.max.max(v);
+        for i in 0..self.history.as_slice().len() {
+            self.smooth(self.history.as_slice()[i]);
         }
     }
+
+    fn smooth(&mut self, (x, y): (f64, f64)) {
+        if self.smoothing != Smoothing::Off {
+            self.smoothed.push((x, self.smoother.push(y)));
+        }
+    }
+}
+
+/// Create a block title for the current value, followed by the smoothed value if available.
+fn title(
+    labeler: impl Fn(f64) -> String,
+    current: f64,
+    smoothing: Smoothing,
+    smoothed: Option<f64>,
+) -> String {
+    match smoothing {
+        Smoothing::Off => labeler(current),
+        _ => format!(
+            "{} ({smoothing}: {})",
+            labeler(current),
+            labeler(smoothed.unwrap_or_default())
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChartValues, Smoothing};
+
+    #[test]
+    fn simple_moving_average() {
+        let values = Smoothing::Sma(2).apply([1.0, 3.0, 5.0, 7.0]);
+        assert_eq!(vec![1.0, 2.0, 4.0, 6.0], values);
+    }
+
+    #[test]
+    fn exponential_moving_average() {
+        let values = Smoothing::Ema(3).apply([2.0, 4.0, 4.0]);
+        assert_eq!(vec![2.0, 3.0, 3.5], values);
+    }
+
+    #[test]
+    fn smooth_incrementally() {
+        let mut before = ChartValues::new(3, 0.0, |v| v.to_string());
+        let mut after = ChartValues::new(3, 0.0, |v| v.to_string());
+        before.set_smoothing(Smoothing::Sma(2));
+
+        for value in [1.0, 3.0, 5.0, 7.0, 9.0] {
+            before.update(value);
+            after.update(value);
+        }
+        after.set_smoothing(Smoothing::Sma(2));
+
+        assert_eq!(
+            [(3.0, 4.0), (4.0, 6.0), (5.0, 8.0)].as_slice(),
+            before.smoothed.as_slice()
+        );
+        // Switching only has the kept history to start from.
+        assert_eq!(
+            [(3.0, 5.0), (4.0, 6.0), (5.0, 8.0)].as_slice(),
+            after.smoothed.as_slice()
+        );
+    }
+
+    #[test]
+    fn parse_smoothing() {
+        assert_eq!(Smoothing::Off, "off".parse().unwrap());
+        assert_eq!(Smoothing::Sma(10), "sma:10".parse().unwrap());
+        assert_eq!(Smoothing::Ema(4), "ema:4".parse().unwrap());
+        assert!("wma:4".parse::<Smoothing>().is_err());
+        assert!("sma".parse::<Smoothing>().is_err());
+    }
 }
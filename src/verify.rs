@@ -0,0 +1,133 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use time::Duration;
+
+use crate::{
+    ffprobe::{self, Format},
+    format_duration,
+    stats::Event,
+    tools,
+};
+
+/// Length in seconds of the stretches at the start and end of the output, that are decoded.
+const DECODE_LENGTH: &str = "5";
+
+/// Verification of the encoded output, by probing it and comparing it with the input.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Verify {
+    /// Maximum allowed difference between input and output duration, in seconds.
+    pub tolerance: f64,
+    /// Expected amount of streams in the output, or the same as the input if not set.
+    pub streams: Option<u32>,
+}
+
+impl Verify {
+    /// Probe the output and check it against the input, recording any problems found. The start
+    /// and end of the output are decoded as well, to catch files that are cut off or corrupt.
+    pub fn run(&self, input: &Format, output: &Path) -> Event {
+        let problems = match ffprobe::run(output) {
+            Ok(format) => {
+                let mut problems = self.check(input, &format);
+                problems.extend(decode(output, "start", &["-t", DECODE_LENGTH]));
+                problems.extend(decode(
+                    output,
+                    "end",
+                    &["-sseof", &format!("-{DECODE_LENGTH}")],
+                ));
+                problems
+            }
+            Err(e) => vec![format!(
+                "failed probing the output: {}",
+                e.to_string().trim()
+            )],
+        };
+
+        Event::Verified { problems }
+    }
+
+    fn check(&self, input: &Format, output: &Format) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if output.size == 0 {
+            problems.push("output is empty".to_owned());
+        }
+
+        if (output.duration - input.duration).abs() > Duration::seconds_f64(self.tolerance) {
+            problems.push(format!(
+                "duration of {} differs from the input's {}",
+                format_duration(output.duration),
+                format_duration(input.duration)
+            ));
+        }
+
+        let streams = self.streams.unwrap_or(input.nb_streams);
+        if output.nb_streams != streams {
+            problems.push(format!(
+                "found {} streams, expected {streams}",
+                output.nb_streams
+            ));
+        }
+
+        problems
+    }
+}
+
+/// Decode a part of the output, selected by the given input options, and stop at the first error.
+fn decode(output: &Path, part: &str, options: &[&str]) -> Option<String> {
+    let result = tools::ffmpeg()
+        .args(["-hide_banner", "-nostdin", "-loglevel", "error", "-xerror"])
+        .args(options)
+        .arg("-i")
+        .arg(output)
+        .args(["-f", "null", "-"])
+        .output();
+
+    match result {
+        Ok(result) if result.status.success() => None,
+        Ok(result) => Some(format!(
+            "failed decoding the {part} of the output: {}",
+            String::from_utf8_lossy(&result.stderr)
+                .lines()
+                .next()
+                .unwrap_or("unknown error")
+        )),
+        Err(e) => Some(format!("failed running FFmpeg to decode the output: {e}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Duration;
+
+    use super::Verify;
+    use crate::ffprobe::Format;
+
+    #[test]
+    fn check_output() {
+        let format = |seconds, nb_streams| Format {
+            nb_streams,
+            duration: Duration::seconds_f64(seconds),
+            size: 1000,
+            ..Format::default()
+        };
+        let verify = Verify {
+            tolerance: 0.5,
+            streams: None,
+        };
+
+        assert!(verify
+            .check(&format(100.0, 2), &format(100.4, 2))
+            .is_empty());
+        assert_eq!(2, verify.check(&format(100.0, 2), &format(98.0, 1)).len());
+
+        let verify = Verify {
+            streams: Some(1),
+            ..verify
+        };
+
+        assert!(verify
+            .check(&format(100.0, 2), &format(100.0, 1))
+            .is_empty());
+    }
+}